@@ -0,0 +1,144 @@
+//! Constant-product AMM implementing the SPL Token Swap `Swap` instruction,
+//! so payout swaps can be tested without the real token-swap program. It
+//! charges no fees and mints no pool tokens. Swap state is written directly
+//! by the tests.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Account as TokenAccount;
+use std::convert::TryInto;
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// SPL Token Swap `Swap` instruction tag.
+pub const SWAP_INSTRUCTION_TAG: u8 = 1;
+
+/// SPL Token Swap `SwapError::ExceededSlippage`.
+pub const EXCEEDED_SLIPPAGE: u32 = 16;
+
+/// State of a swap pool: its authority bump and its two token vaults, owned by
+/// the authority `[swap, [nonce]]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapState {
+    pub nonce: u8,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+}
+
+impl SwapState {
+    pub const LEN: usize = 1 + 32 + 32;
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::LEN);
+        data.push(self.nonce);
+        data.extend_from_slice(self.token_a.as_ref());
+        data.extend_from_slice(self.token_b.as_ref());
+        data
+    }
+
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(SwapState {
+            nonce: data[0],
+            token_a: Pubkey::new(&data[1..33]),
+            token_b: Pubkey::new(&data[33..65]),
+        })
+    }
+}
+
+/// Amount a constant-product pool pays out for `amount_in`.
+pub fn amount_out(reserve_in: u64, reserve_out: u64, amount_in: u64) -> u64 {
+    ((reserve_out as u128) * (amount_in as u128) / (reserve_in as u128 + amount_in as u128)) as u64
+}
+
+/// Accounts are those of SPL Token Swap `Swap`: `[swap, swap_authority,
+/// user_transfer_authority, source, swap_source, swap_destination,
+/// destination, pool_mint, fee_account, token_program]`.
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if instruction_data.len() != 17 || instruction_data[0] != SWAP_INSTRUCTION_TAG {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount_in = u64::from_le_bytes(instruction_data[1..9].try_into().unwrap());
+    let minimum_amount_out = u64::from_le_bytes(instruction_data[9..17].try_into().unwrap());
+
+    let account_info_iter = &mut accounts.iter();
+    let swap = next_account_info(account_info_iter)?;
+    let swap_authority = next_account_info(account_info_iter)?;
+    let user_transfer_authority = next_account_info(account_info_iter)?;
+    let source = next_account_info(account_info_iter)?;
+    let swap_source = next_account_info(account_info_iter)?;
+    let swap_destination = next_account_info(account_info_iter)?;
+    let destination = next_account_info(account_info_iter)?;
+    let _pool_mint = next_account_info(account_info_iter)?;
+    let _fee_account = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if swap.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let state = SwapState::unpack(&swap.try_borrow_data()?)?;
+    let authority = Pubkey::create_program_address(&[swap.key.as_ref(), &[state.nonce]], program_id)?;
+    if *swap_authority.key != authority {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let vaults = (*swap_source.key, *swap_destination.key);
+    if vaults != (state.token_a, state.token_b) && vaults != (state.token_b, state.token_a) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let reserve_in = TokenAccount::unpack(&swap_source.try_borrow_data()?)?.amount;
+    let reserve_out = TokenAccount::unpack(&swap_destination.try_borrow_data()?)?.amount;
+    let out = amount_out(reserve_in, reserve_out, amount_in);
+    if out < minimum_amount_out {
+        return Err(ProgramError::Custom(EXCEEDED_SLIPPAGE));
+    }
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            swap_source.key,
+            user_transfer_authority.key,
+            &[],
+            amount_in,
+        )?,
+        &[
+            source.clone(),
+            swap_source.clone(),
+            user_transfer_authority.clone(),
+            token_program.clone(),
+        ],
+    )?;
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            swap_destination.key,
+            destination.key,
+            swap_authority.key,
+            &[],
+            out,
+        )?,
+        &[
+            swap_destination.clone(),
+            destination.clone(),
+            swap_authority.clone(),
+            token_program.clone(),
+        ],
+        &[&[swap.key.as_ref(), &[state.nonce]]],
+    )?;
+
+    Ok(())
+}
@@ -1,11 +1,39 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{clock, program_option::COption, sysvar};
+use anchor_lang::solana_program::hash::{hash, hashv};
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed, set_return_data};
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::system_instruction;
+use std::convert::TryInto;
+use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::{self, Mint, Token, TokenAccount};
-use port_anchor_adaptor::{deposit_reserve, Deposit, redeem, Redeem};
+use spl_token::instruction::AuthorityType;
+use port_anchor_adaptor::{claim_reward, deposit_reserve, ClaimReward, Deposit, redeem, Redeem};
+use port_anchor_adaptor::{PortObligation, PortStakeAccount};
 use port_anchor_adaptor::port_accessor::{exchange_rate};
+use port_variable_rate_lending_instructions::instruction::{
+    deposit_obligation_collateral, refresh_obligation, refresh_reserve, withdraw_obligation_collateral,
+};
+use port_variable_rate_lending_instructions::state::Reserve as PortReserve;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// Port Fi. staking program.
+pub mod port_staking_program {
+    anchor_lang::declare_id!("stkarvwmSzv2BygN5e2LeTwimTczLWHCKPKGC2zVLiQ");
+}
+
+/// Pyth oracle program, owner of the price accounts pools are valued with.
+pub mod pyth_program {
+    anchor_lang::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+/// SPL Token Swap program, the AMM payouts are swapped through.
+pub mod spl_token_swap_program {
+    anchor_lang::declare_id!("SwaPpA9LAaLfeLi3a68M4DjnLqgtticKg6CnyNwgAC8");
+}
+
 #[program]
 pub mod lossless_donation {
     use super::*;
@@ -20,17 +48,51 @@ pub mod lossless_donation {
         donation_pool.token_vault = ctx.accounts.token_vault.key();
         donation_pool.yield_token_mint = ctx.accounts.yield_token_mint.key();
         donation_pool.yield_token_vault = ctx.accounts.yield_token_vault.key();
+        donation_pool.reserve = ctx.accounts.reserve.key();
+        donation_pool.authority = ctx.accounts.authority.key();
+        // No protocol fee until the authority sets one.
+        donation_pool.treasury_vault = ctx.accounts.donation_vault.key();
+        donation_pool.protocol_fee_bps = 0;
+        donation_pool.total_protocol_fees = 0;
+        // Pure charity pool until the authority enables the lottery.
+        donation_pool.prize_vault = ctx.accounts.donation_vault.key();
+        donation_pool.prize_bps = 0;
+        donation_pool.total_prizes = 0;
+        donation_pool.draw_round = 0;
+        donation_pool.draw_state = DRAW_STATE_IDLE;
+        // Donations are paid out at harvest until streaming is enabled.
+        donation_pool.stream_vault = ctx.accounts.donation_vault.key();
+        donation_pool.stream_duration = 0;
+        // Donations only go to a quadratic funding round while one is running.
+        donation_pool.qf_vault = ctx.accounts.donation_vault.key();
+        // Donations are paid in `token_mint` until a payout mint is set.
+        donation_pool.payout_vault = ctx.accounts.donation_vault.key();
+        donation_pool.payout_pending = 0;
+        donation_pool.governance_epoch = 0;
+        donation_pool.allocated_weight_bps = 0;
+        donation_pool.allocated_principal = 0;
+        donation_pool.migration_execute_after = 0;
+        donation_pool.total_rewards_donated = 0;
         donation_pool.user_stake_count = 0;
+        donation_pool.harvest_count = 0;
+        donation_pool.last_harvest_timestamp = Clock::get()?.unix_timestamp;
+        donation_pool.acc_interest_per_share = 0;
+        donation_pool.accrued_interest = 0;
         donation_pool.nonce = nonce;
 
         Ok(())
     }
 
     pub fn create_user(ctx: Context<CreateUser>, nonce: u8) -> Result<()> {
+        // The account may already exist; only a new one is set up and counted.
         let user = &mut ctx.accounts.user;
+        if user.owner != Pubkey::default() {
+            return Ok(());
+        }
         user.donation_pool = *ctx.accounts.donation_pool.to_account_info().key;
         user.owner = *ctx.accounts.owner.key;
         user.balance_staked = 0;
+        user.interest_debt = 0;
         user.nonce = nonce;
 
         let pool = &mut ctx.accounts.donation_pool;
@@ -39,123 +101,204 @@ pub mod lossless_donation {
         Ok(())
     }
 
-    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
-        if amount == 0 {
-            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
-        }
+    /// Pools with reward staking take their Port staking accounts as remaining
+    /// accounts (see `split_staking_accounts`), here and in every instruction
+    /// that moves primary collateral. `unstake` by a user who voted in the
+    /// running epoch also takes their `[vote, proposal]` after those, then the
+    /// position accounts to redeem from when the primary reserve is short
+    /// (see `redeem_from_positions`).
+    pub fn stake<'info>(ctx: Context<'_, '_, '_, 'info, Stake<'info>>, amount: u64) -> Result<()> {
+        stake_tokens(ctx, amount, false)
+    }
 
-        let pool = &mut ctx.accounts.donation_pool;
+    pub fn stake_locked<'info>(ctx: Context<'_, '_, '_, 'info, Stake<'info>>, amount: u64, tier: u8) -> Result<()> {
+        let (lock_duration, lock_multiplier_bps) = match LOCK_TIERS.get(tier as usize) {
+            Some(lock_tier) => *lock_tier,
+            None => return Err(ErrorCode::InvalidLockTier.into()),
+        };
 
-        ctx.accounts.user.balance_staked = ctx
-            .accounts
-            .user
-            .balance_staked
-            .checked_add(amount)
-            .unwrap();
+        let now = ctx.accounts.clock.unix_timestamp;
+        let user = &mut ctx.accounts.user;
+
+        // Settle first so donations accrued before the lock aren't boosted.
+        user.settle_donation(&ctx.accounts.donation_pool, now);
+        user.lock_multiplier_bps = std::cmp::max(user.lock_multiplier(now), lock_multiplier_bps);
+        user.lock_until = std::cmp::max(user.lock_until, now.checked_add(lock_duration).unwrap());
+
+        stake_tokens(ctx, amount, true)
+    }
 
-        pool.total_deposited = pool.total_deposited.checked_add(amount as u128).unwrap();
+    pub fn unstake<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>, amount: u64) -> Result<()> {
+        unstake_tokens(ctx, amount)
+    }
+
+    pub fn stake_sol<'info>(ctx: Context<'_, '_, '_, 'info, StakeSol<'info>>, amount: u64, wsol_nonce: u8) -> Result<()> {
+        let user_key = ctx.accounts.stake.user.key();
+        let wsol_seeds = &[b"wsol".as_ref(), user_key.as_ref(), &[wsol_nonce]];
 
+        // Wrap the owner's lamports into a temporary WSOL account.
+        create_wsol_account(
+            &ctx.accounts.stake.owner,
+            &ctx.accounts.wsol_account,
+            &ctx.accounts.stake.token_mint,
+            &ctx.accounts.system_program,
+            &ctx.accounts.stake.token_program,
+            &ctx.accounts.rent,
+            &wsol_seeds[..],
+            amount,
+        )?;
 
+        stake_tokens(
+            Context::new(ctx.program_id, &mut ctx.accounts.stake, ctx.remaining_accounts),
+            amount,
+            false,
+        )?;
 
-        // Transfer tokens into the stake vault.
+        // Close the temporary account, returning its rent to the owner.
         {
             let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.stake_from_account.to_account_info(),
-                    to: ctx.accounts.token_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+                ctx.accounts.stake.token_program.to_account_info(),
+                token::CloseAccount {
+                    account: ctx.accounts.wsol_account.to_account_info(),
+                    destination: ctx.accounts.stake.owner.to_account_info(),
+                    authority: ctx.accounts.stake.owner.to_account_info(),
                 },
             );
-            token::transfer(cpi_ctx, amount)?;
+            token::close_account(cpi_ctx)?;
         }
 
-        // deposit into Port Finance
-        {
-            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
-            let pool_signer = &[&seeds[..]];
+        Ok(())
+    }
 
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.lending_program.clone(),
-                Deposit {
-                    source_liquidity: ctx.accounts.token_vault.to_account_info(),
-                    destination_collateral: ctx.accounts.yield_token_vault.to_account_info(),
-                    reserve: ctx.accounts.reserve.clone(),
-                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-                    reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
-                    lending_market: ctx.accounts.lending_market.clone(),
-                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-                    transfer_authority: ctx.accounts.transfer_authority.clone(),
-                    clock: ctx.accounts.clock.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info()
+    pub fn unstake_sol<'info>(ctx: Context<'_, '_, '_, 'info, UnstakeSol<'info>>, amount: u64, wsol_nonce: u8) -> Result<()> {
+        let user_key = ctx.accounts.unstake.user.key();
+        let wsol_seeds = &[b"wsol".as_ref(), user_key.as_ref(), &[wsol_nonce]];
+
+        // Create a temporary WSOL account to unwrap through.
+        create_wsol_account(
+            &ctx.accounts.unstake.owner,
+            &ctx.accounts.wsol_account,
+            &ctx.accounts.unstake.token_mint,
+            &ctx.accounts.system_program,
+            &ctx.accounts.unstake.token_program,
+            &ctx.accounts.rent,
+            &wsol_seeds[..],
+            0,
+        )?;
+
+        unstake_tokens(
+            Context::new(ctx.program_id, &mut ctx.accounts.unstake, ctx.remaining_accounts),
+            amount,
+        )?;
+
+        // Close the temporary account, unwrapping to the owner's wallet.
+        {
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.unstake.token_program.to_account_info(),
+                token::CloseAccount {
+                    account: ctx.accounts.wsol_account.to_account_info(),
+                    destination: ctx.accounts.unstake.owner.to_account_info(),
+                    authority: ctx.accounts.unstake.owner.to_account_info(),
                 },
-                pool_signer
             );
-            deposit_reserve(cpi_ctx, ctx.accounts.token_vault.amount)?;
+            token::close_account(cpi_ctx)?;
         }
 
         Ok(())
     }
 
-    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
-        if amount == 0 {
-            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
-        } if ctx.accounts.user.balance_staked < amount {
-            return Err(ErrorCode::InsufficientFundUnstake.into());
-        }
+    /// Pools with reward staking lead the remaining accounts with their staking
+    /// accounts. Every matching pool registered on the pool follows as a
+    /// `[matching_pool, matching_vault]` pair, in order. Pools with a payout
+    /// mint escrow the donation in `payout_vault` for `swap_payout`.
+    pub fn transfer_interest_to_charity<'info>(
+        ctx: Context<'_, '_, '_, 'info, TransferInterestToCharity<'info>>,
+    ) -> Result<()> {
+        let (staking_accounts, matching_accounts) = split_staking_accounts(&ctx.accounts.donation_pool, ctx.remaining_accounts)?;
 
-        let pool = &mut ctx.accounts.donation_pool;
+        harvest_interest(ctx.program_id, ctx.accounts, staking_accounts, matching_accounts)?;
 
-        ctx.accounts.user.balance_staked = ctx
-            .accounts
-            .user
-            .balance_staked
-            .checked_sub(amount)
-            .unwrap();
+        Ok(())
+    }
 
-        pool.total_deposited = pool.total_deposited.checked_sub(amount as u128).unwrap(); 
+    pub fn fund_match(ctx: Context<FundMatch>, match_bps: u16, end_timestamp: i64, amount: u64) -> Result<()> {
+        if amount == 0 || match_bps == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+        if end_timestamp <= ctx.accounts.clock.unix_timestamp {
+            return Err(ErrorCode::MatchingPeriodEnded.into());
+        }
 
-        {
-            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
-            let pool_signer = &[&seeds[..]];
+        let matching_pool = &mut ctx.accounts.matching_pool;
+        matching_pool.sponsor = ctx.accounts.sponsor.key();
+        matching_pool.donation_pool = ctx.accounts.donation_pool.key();
+        matching_pool.vault = ctx.accounts.matching_vault.key();
+        matching_pool.match_bps = match_bps;
+        matching_pool.end_timestamp = end_timestamp;
+        matching_pool.budget = amount;
+        matching_pool.total_matched = 0;
 
-            let cpi_ctx = CpiContext::new_with_signer(
+        // Transfer the sponsor's budget into the matching vault.
+        {
+            let cpi_ctx = CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Approve {
-                    to: ctx.accounts.yield_token_vault.to_account_info(),
-                    delegate: ctx.accounts.transfer_authority.to_account_info(),
-                    authority: ctx.accounts.pool_signer.to_account_info(), //todo use user account as signer
+                token::Transfer {
+                    from: ctx.accounts.fund_from_account.to_account_info(),
+                    to: ctx.accounts.matching_vault.to_account_info(),
+                    authority: ctx.accounts.sponsor.to_account_info(),
                 },
-                pool_signer
             );
-            token::approve(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+            token::transfer(cpi_ctx, amount)?;
         }
 
-        // withdraw from Port Finance to user's balance
-        {
-            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
-            let pool_signer = &[&seeds[..]];
+        Ok(())
+    }
 
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.lending_program.clone(),
-                Redeem {
-                    source_collateral: ctx.accounts.yield_token_vault.to_account_info(),
-                    destination_liquidity: ctx.accounts.token_vault.to_account_info(),
-                    reserve: ctx.accounts.reserve.clone(),
-                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-                    reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
-                    lending_market: ctx.accounts.lending_market.clone(),
-                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
-                    clock: ctx.accounts.clock.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info()
-                },
-                pool_signer
-            );
-            redeem(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+    /// Lets a funded matching pool match the pool's harvests. Registration
+    /// slots are scarce, so the authority (or council) picks the sponsors.
+    pub fn register_match(ctx: Context<RegisterMatch>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_REGISTER_MATCH, 0, &[ctx.accounts.matching_pool.key()]),
+        )?;
+
+        let count = pool.matching_pool_count as usize;
+        if pool.matching_pools[..count].contains(&ctx.accounts.matching_pool.key()) {
+            return Err(ErrorCode::MatchingPoolAlreadyRegistered.into());
+        }
+        if count >= MAX_MATCHING_POOLS {
+            return Err(ErrorCode::MatchingPoolsFull.into());
         }
+        if ctx.accounts.clock.unix_timestamp >= ctx.accounts.matching_pool.end_timestamp {
+            return Err(ErrorCode::MatchingPeriodEnded.into());
+        }
+        pool.matching_pools[count] = ctx.accounts.matching_pool.key();
+        pool.matching_pool_count += 1;
 
-        // Transfer tokens into the user's personal token vault.
+        Ok(())
+    }
+
+    /// Returns the unspent budget to the sponsor once the matching period has
+    /// ended, or at any time while the matching pool isn't registered.
+    pub fn reclaim_match(ctx: Context<ReclaimMatch>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+        let count = pool.matching_pool_count as usize;
+        let registered = pool.matching_pools[..count].contains(&ctx.accounts.matching_pool.key());
+        if registered && ctx.accounts.clock.unix_timestamp < ctx.accounts.matching_pool.end_timestamp {
+            return Err(ErrorCode::MatchingPeriodNotEnded.into());
+        }
+
+        let pool = &mut ctx.accounts.donation_pool;
+        let amount = ctx.accounts.matching_vault.amount;
+        ctx.accounts.matching_pool.budget = 0;
+        pool.remove_matching_pool(ctx.accounts.matching_pool.key());
+
+        // Return the unspent budget to the sponsor.
         {
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
@@ -163,88 +306,285 @@ pub mod lossless_donation {
             let cpi_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 token::Transfer {
-                    from: ctx.accounts.token_vault.to_account_info(),
+                    from: ctx.accounts.matching_vault.to_account_info(),
                     to: ctx.accounts.receiving_vault.to_account_info(),
-                    authority: ctx.accounts.pool_signer.to_account_info(), //todo use user account as signer
+                    authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer
             );
             token::transfer(cpi_ctx, amount)?;
         }
 
-        // deposit into Port Finance
+        Ok(())
+    }
+
+    pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, protocol_fee_bps: u16) -> Result<()> {
+        if protocol_fee_bps > MAX_PROTOCOL_FEE_BPS {
+            return Err(ErrorCode::ProtocolFeeTooHigh.into());
+        }
+
+        let pool = &mut ctx.accounts.donation_pool;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_SET_PROTOCOL_FEE,
+                protocol_fee_bps as u64,
+                &[ctx.accounts.treasury_vault.key()],
+            ),
+        )?;
+        pool.protocol_fee_bps = protocol_fee_bps;
+        pool.treasury_vault = ctx.accounts.treasury_vault.key();
+
+        Ok(())
+    }
+
+    pub fn set_lottery(ctx: Context<SetLottery>, prize_bps: u16) -> Result<()> {
+        if prize_bps as u64 > BPS_DENOMINATOR {
+            return Err(ErrorCode::InvalidPrizeSplit.into());
+        }
+
+        let pool = &mut ctx.accounts.donation_pool;
+        // A committed or drawn round holds its bond and prize in the vault.
+        if pool.draw_state != DRAW_STATE_IDLE && pool.draw_state != DRAW_STATE_OPEN
+            && ctx.accounts.prize_vault.key() != pool.prize_vault
         {
-            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
-            let pool_signer = &[&seeds[..]];
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_SET_LOTTERY, prize_bps as u64, &[ctx.accounts.prize_vault.key()]),
+        )?;
+        pool.prize_bps = prize_bps;
+        pool.prize_vault = ctx.accounts.prize_vault.key();
 
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.lending_program.clone(),
-                Deposit {
-                    source_liquidity: ctx.accounts.token_vault.to_account_info(),
-                    destination_collateral: ctx.accounts.yield_token_vault.to_account_info(),
-                    reserve: ctx.accounts.reserve.clone(),
-                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-                    reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
-                    lending_market: ctx.accounts.lending_market.clone(),
-                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-                    transfer_authority: ctx.accounts.transfer_authority.clone(),
-                    clock: ctx.accounts.clock.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info()
-                },
-                pool_signer
-            );
-            deposit_reserve(cpi_ctx, ctx.accounts.token_vault.amount)?;
+        Ok(())
+    }
+
+    pub fn start_draw(ctx: Context<StartDraw>, duration: i64) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.draw_state != DRAW_STATE_IDLE {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+        if duration <= 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
         }
+        let now = ctx.accounts.clock.unix_timestamp;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            now,
+            CouncilAction::new(COUNCIL_ACTION_START_DRAW, duration as u64, &[]),
+        )?;
+
+        pool.draw_round = pool.draw_round.checked_add(1).unwrap();
+        pool.draw_start_timestamp = now;
+        pool.draw_end_timestamp = now.checked_add(duration).unwrap();
+        pool.draw_ticket_total = 0;
+        pool.draw_state = DRAW_STATE_OPEN;
 
         Ok(())
     }
 
-    pub fn transfer_interest_to_charity(ctx: Context<TransferInterestToCharity>) -> Result<()> {
+    /// Permissionless, so anyone can enter every staker once the round ends.
+    pub fn enter_draw(ctx: Context<EnterDraw>) -> Result<()> {
         let pool = &mut ctx.accounts.donation_pool;
+        let user = &mut ctx.accounts.user;
+        let now = ctx.accounts.clock.unix_timestamp;
 
-        {
-            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
-            let pool_signer = &[&seeds[..]];
+        if pool.draw_state != DRAW_STATE_OPEN || now < pool.draw_end_timestamp {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+        if user.ticket_round == pool.draw_round {
+            return Err(ErrorCode::AlreadyEnteredDraw.into());
+        }
 
-            let cpi_ctx = CpiContext::new_with_signer(
+        // Tickets are the user's balance-seconds staked during the round.
+        user.accrue_draw_weight(pool, now);
+        user.ticket_round = pool.draw_round;
+        user.ticket_start = pool.draw_ticket_total;
+        user.ticket_end = user.ticket_start.checked_add(user.draw_weight).unwrap();
+        pool.draw_ticket_total = user.ticket_end;
+
+        Ok(())
+    }
+
+    /// Commits to the draw secret and to the hash of a future slot. Stakers get
+    /// `DRAW_ENTRY_PERIOD` after the round ends to be entered first.
+    ///
+    /// The caller bonds as much as the prize vault holds. The bond is refunded
+    /// when the draw is revealed and added to the prize if it isn't, so
+    /// withholding an unwelcome outcome costs a prize.
+    pub fn commit_draw(ctx: Context<CommitDraw>, commitment: [u8; 32]) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let entry_end = pool.draw_end_timestamp.checked_add(DRAW_ENTRY_PERIOD).unwrap();
+        if pool.draw_state != DRAW_STATE_OPEN || ctx.accounts.clock.unix_timestamp < entry_end {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_COMMIT_DRAW, 0, &[Pubkey::new_from_array(commitment)]),
+        )?;
+
+        let bond = ctx.accounts.prize_vault.amount;
+        pool.draw_commitment = commitment;
+        pool.draw_reveal_slot = ctx.accounts.clock.slot.checked_add(DRAW_REVEAL_DELAY_SLOTS).unwrap();
+        pool.draw_bond = bond;
+        pool.draw_bond_vault = ctx.accounts.bond_from_account.key();
+        pool.draw_state = DRAW_STATE_COMMITTED;
+
+        // Transfer the bond into the prize vault.
+        if bond > 0 {
+            let cpi_ctx = CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Approve {
-                    to: ctx.accounts.yield_token_vault.to_account_info(),
-                    delegate: ctx.accounts.transfer_authority.to_account_info(),
-                    authority: ctx.accounts.pool_signer.to_account_info(), //todo use user account as signer
+                token::Transfer {
+                    from: ctx.accounts.bond_from_account.to_account_info(),
+                    to: ctx.accounts.prize_vault.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
                 },
-                pool_signer
             );
-            token::approve(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+            token::transfer(cpi_ctx, bond)?;
         }
 
-        // withdraw from Port Finance to user's balance
-        {
+        Ok(())
+    }
+
+    /// Permissionless. Records the hash of the slot committed to while the
+    /// `SlotHashes` sysvar still holds it, leaving `DRAW_REVEAL_PERIOD` for
+    /// the reveal to be approved.
+    pub fn record_draw_seed(ctx: Context<RecordDrawSeed>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.draw_state != DRAW_STATE_COMMITTED || ctx.accounts.clock.slot <= pool.draw_reveal_slot {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+
+        let slot_hashes = ctx.accounts.slot_hashes.try_borrow_data()?;
+        pool.draw_seed = match slot_hash_at(&slot_hashes, pool.draw_reveal_slot) {
+            Some(slot_hash) => slot_hash,
+            None => return Err(ErrorCode::DrawRevealExpired.into()),
+        };
+        pool.draw_reveal_deadline = ctx.accounts.clock.unix_timestamp.checked_add(DRAW_REVEAL_PERIOD).unwrap();
+        pool.draw_state = DRAW_STATE_SEEDED;
+
+        Ok(())
+    }
+
+    /// Reveals the committed secret and mixes it with the recorded hash of the
+    /// slot committed to, so neither the authority nor the leader alone
+    /// controls the outcome.
+    pub fn draw_winner(ctx: Context<DrawWinner>, secret: [u8; 32]) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.draw_state != DRAW_STATE_SEEDED {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+        if hash(&secret).to_bytes() != pool.draw_commitment {
+            return Err(ErrorCode::InvalidDrawSecret.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_DRAW_WINNER, 0, &[Pubkey::new_from_array(secret)]),
+        )?;
+
+        let bond = pool.draw_bond;
+        pool.draw_bond = 0;
+        if pool.draw_ticket_total == 0 {
+            // Nobody entered, the prize rolls over to the next round.
+            pool.draw_state = DRAW_STATE_IDLE;
+        } else {
+            let seed = hashv(&[&secret, &pool.draw_seed]).to_bytes();
+            let mut random = [0u8; 16];
+            random.copy_from_slice(&seed[..16]);
+
+            // The prize is fixed now; later harvests go to the next round.
+            pool.draw_prize = ctx.accounts.prize_vault.amount.checked_sub(bond).unwrap();
+            pool.draw_winning_ticket = u128::from_le_bytes(random) % pool.draw_ticket_total;
+            pool.draw_claim_deadline = ctx.accounts.clock.unix_timestamp.checked_add(DRAW_CLAIM_PERIOD).unwrap();
+            pool.draw_state = DRAW_STATE_DRAWN;
+        }
+
+        // Refund the bond.
+        if bond > 0 {
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
 
             let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.lending_program.clone(),
-                Redeem {
-                    source_collateral: ctx.accounts.yield_token_vault.to_account_info(),
-                    destination_liquidity: ctx.accounts.token_vault.to_account_info(),
-                    reserve: ctx.accounts.reserve.clone(),
-                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-                    reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
-                    lending_market: ctx.accounts.lending_market.clone(),
-                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
-                    clock: ctx.accounts.clock.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info()
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.prize_vault.to_account_info(),
+                    to: ctx.accounts.bond_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer
             );
-            redeem(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+            token::transfer(cpi_ctx, bond)?;
         }
 
-        let interest = ctx.accounts.token_vault.amount - pool.total_deposited as u64;
+        Ok(())
+    }
 
-        // Transfer tokens into the user's personal token vault.
+    /// Permissionless reset of a draw whose seed was not recorded while its slot
+    /// hash was available, that was not revealed in time, or whose prize was
+    /// not claimed in time. The prize rolls over to the next round, along with
+    /// the bond of an unrevealed draw.
+    pub fn expire_draw(ctx: Context<ExpireDraw>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let clock = &ctx.accounts.clock;
+
+        let seed_expired = pool.draw_state == DRAW_STATE_COMMITTED
+            && clock.slot > pool.draw_reveal_slot.checked_add(DRAW_REVEAL_WINDOW_SLOTS).unwrap();
+        let reveal_expired = pool.draw_state == DRAW_STATE_SEEDED
+            && clock.unix_timestamp > pool.draw_reveal_deadline;
+        let claim_expired = pool.draw_state == DRAW_STATE_DRAWN
+            && clock.unix_timestamp > pool.draw_claim_deadline;
+        if !seed_expired && !reveal_expired && !claim_expired {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+
+        pool.draw_bond = 0;
+        pool.draw_prize = 0;
+        pool.draw_state = DRAW_STATE_IDLE;
+
+        Ok(())
+    }
+
+    pub fn claim_prize(ctx: Context<ClaimPrize>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let user = &ctx.accounts.user;
+
+        if pool.draw_state != DRAW_STATE_DRAWN
+            || ctx.accounts.clock.unix_timestamp > pool.draw_claim_deadline
+        {
+            return Err(ErrorCode::InvalidDrawState.into());
+        }
+        if user.ticket_round != pool.draw_round
+            || pool.draw_winning_ticket < user.ticket_start
+            || pool.draw_winning_ticket >= user.ticket_end
+        {
+            return Err(ErrorCode::NotDrawWinner.into());
+        }
+
+        let prize = pool.draw_prize;
+        pool.draw_prize = 0;
+        pool.draw_state = DRAW_STATE_IDLE;
+
+        // Transfer the prize to the winner.
         {
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
@@ -252,198 +592,4586 @@ pub mod lossless_donation {
             let cpi_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 token::Transfer {
-                    from: ctx.accounts.token_vault.to_account_info(),
-                    to: ctx.accounts.donation_vault.to_account_info(),
-                    authority: ctx.accounts.pool_signer.to_account_info(), //todo use user account as signer
+                    from: ctx.accounts.prize_vault.to_account_info(),
+                    to: ctx.accounts.receiving_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer
             );
-            token::transfer(cpi_ctx, interest)?;
+            token::transfer(cpi_ctx, prize)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_stream(ctx: Context<SetStream>, stream_duration: i64) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if stream_duration < 0 {
+            return Err(ErrorCode::InvalidStreamDuration.into());
+        }
+        if stream_duration != 0 && pool.payout_mint != Pubkey::default() {
+            return Err(ErrorCode::PayoutWithStream.into());
         }
+        if pool.stream_vault != ctx.accounts.stream_vault.key() && pool.stream_amount > pool.stream_released {
+            return Err(ErrorCode::StreamInProgress.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_SET_STREAM,
+                stream_duration as u64,
+                &[ctx.accounts.stream_vault.key()],
+            ),
+        )?;
+
+        pool.stream_duration = stream_duration;
+        pool.stream_vault = ctx.accounts.stream_vault.key();
+
+        Ok(())
+    }
+
+    pub fn release_stream(ctx: Context<ReleaseStream>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
 
-        pool.total_donated += interest as u128;
+        let released = pool.stream_releasable(ctx.accounts.clock.unix_timestamp);
+        if released == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+        pool.stream_released = pool.stream_released.checked_add(released).unwrap();
 
-        // deposit into Port Finance
+        // Transfer the vested donation to the charity.
         {
             let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
             let pool_signer = &[&seeds[..]];
 
             let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.lending_program.clone(),
-                Deposit {
-                    source_liquidity: ctx.accounts.token_vault.to_account_info(),
-                    destination_collateral: ctx.accounts.yield_token_vault.to_account_info(),
-                    reserve: ctx.accounts.reserve.clone(),
-                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-                    reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
-                    lending_market: ctx.accounts.lending_market.clone(),
-                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-                    transfer_authority: ctx.accounts.transfer_authority.clone(),
-                    clock: ctx.accounts.clock.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info()
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.stream_vault.to_account_info(),
+                    to: ctx.accounts.donation_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
                 },
                 pool_signer
             );
-            deposit_reserve(cpi_ctx, ctx.accounts.token_vault.amount)?;
+            token::transfer(cpi_ctx, released)?;
         }
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(nonce: u8)]
-pub struct Initialize<'info> {
-    #[account(zero)]
-    pub donation_pool: Account<'info, DonationPool>,
+    pub fn set_governance(ctx: Context<SetGovernance>, epoch_duration: i64) -> Result<()> {
+        if epoch_duration <= 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
 
-    pub token_mint: Account<'info, Mint>,
-    #[account(
-        mut,
-        constraint = token_vault.mint == token_mint.key(),
-        constraint = token_vault.owner == signer.key(),
-    )]
-    pub token_vault: Account<'info, TokenAccount>,
+        let pool = &mut ctx.accounts.donation_pool;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_SET_GOVERNANCE, epoch_duration as u64, &[]),
+        )?;
+        pool.epoch_duration = epoch_duration;
+        if pool.governance_epoch == 0 {
+            pool.start_epoch(ctx.accounts.clock.unix_timestamp);
+        }
 
-    pub yield_token_mint: Account<'info, Mint>,
+        Ok(())
+    }
+
+    pub fn nominate_charity(ctx: Context<NominateCharity>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+        if pool.governance_epoch == 0 || ctx.accounts.clock.unix_timestamp >= pool.epoch_end_timestamp {
+            return Err(ErrorCode::EpochNotActive.into());
+        }
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.donation_pool = pool.key();
+        proposal.epoch = pool.governance_epoch;
+        proposal.donation_wallet = ctx.accounts.donation_address.key();
+        proposal.donation_vault = ctx.accounts.donation_vault.key();
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.votes = 0;
+
+        Ok(())
+    }
+
+    pub fn vote_charity(ctx: Context<VoteCharity>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let proposal = &mut ctx.accounts.proposal;
+        if ctx.accounts.clock.unix_timestamp >= pool.epoch_end_timestamp {
+            return Err(ErrorCode::EpochNotActive.into());
+        }
+
+        // Locked stake votes with its multiplier.
+        let now = ctx.accounts.clock.unix_timestamp;
+        let weight = ctx.accounts.user.boosted(ctx.accounts.user.balance_staked, now) as u128;
+        if weight == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        ctx.accounts.user.voted_epoch = pool.governance_epoch;
+
+        let vote = &mut ctx.accounts.vote;
+        vote.user = ctx.accounts.user.key();
+        vote.proposal = proposal.key();
+        vote.epoch = pool.governance_epoch;
+        vote.weight = weight;
+
+        proposal.votes = proposal.votes.checked_add(weight).unwrap();
+        if proposal.votes > pool.leading_votes {
+            pool.leading_proposal = proposal.key();
+            pool.leading_votes = proposal.votes;
+        }
+
+        Ok(())
+    }
+
+    /// Permissionless. Makes `proposal` the epoch's leader if it has more
+    /// votes than the current one, which can lose votes to unstaking.
+    pub fn update_leading_proposal(ctx: Context<UpdateLeadingProposal>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let proposal = &ctx.accounts.proposal;
+        if proposal.votes <= pool.leading_votes {
+            return Err(ErrorCode::NotLeadingProposal.into());
+        }
+
+        pool.leading_proposal = proposal.key();
+        pool.leading_votes = proposal.votes;
+
+        Ok(())
+    }
+
+    /// Permissionless, `EPOCH_TALLY_PERIOD` after the epoch ends. Pass the
+    /// epoch's leading proposal, or any account when nobody voted. A charity
+    /// change first pays out the rest of the current stream and any donations
+    /// waiting for a payout swap to the outgoing charity.
+    pub fn finalize_epoch(ctx: Context<FinalizeEpoch>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let now = ctx.accounts.clock.unix_timestamp;
+        let tally_end = pool.epoch_end_timestamp.checked_add(EPOCH_TALLY_PERIOD).unwrap();
+        if pool.governance_epoch == 0 || now < tally_end {
+            return Err(ErrorCode::EpochNotEnded.into());
+        }
+
+        if pool.leading_votes > 0 {
+            let proposal: Account<CharityProposal> = Account::try_from(&ctx.accounts.proposal)?;
+            if proposal.key() != pool.leading_proposal {
+                return Err(ErrorCode::NotLeadingProposal.into());
+            }
+
+            // The rest of the current stream and the donations waiting for a
+            // payout swap were harvested for the old charity.
+            flush_stream(
+                pool,
+                &ctx.accounts.stream_vault,
+                &ctx.accounts.donation_vault,
+                &ctx.accounts.pool_signer,
+                &ctx.accounts.token_program,
+            )?;
+            flush_payout(
+                pool,
+                &ctx.accounts.payout_vault,
+                &ctx.accounts.donation_vault,
+                &ctx.accounts.pool_signer,
+                &ctx.accounts.token_program,
+            )?;
+
+            pool.change_charity(proposal.donation_wallet, proposal.donation_vault);
+        }
+
+        pool.start_epoch(now);
+
+        Ok(())
+    }
+
+    /// The matching pot is whatever sits in `matching_vault` at finalization.
+    /// Until then, the pool's harvested donations collect in `interest_vault`
+    /// and are split by the users' allocations.
+    pub fn create_qf_round(ctx: Context<CreateQfRound>, end_timestamp: i64) -> Result<()> {
+        if end_timestamp <= ctx.accounts.clock.unix_timestamp {
+            return Err(ErrorCode::QfRoundEnded.into());
+        }
+
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.qf_round != Pubkey::default() {
+            return Err(ErrorCode::QfRoundActive.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_CREATE_QF_ROUND,
+                end_timestamp as u64,
+                &[ctx.accounts.qf_round.key()],
+            ),
+        )?;
+        pool.qf_round = ctx.accounts.qf_round.key();
+        pool.qf_vault = ctx.accounts.interest_vault.key();
+
+        let round = &mut ctx.accounts.qf_round;
+        round.donation_pool = ctx.accounts.donation_pool.key();
+        round.authority = ctx.accounts.authority.key();
+        round.matching_vault = ctx.accounts.matching_vault.key();
+        round.interest_vault = ctx.accounts.interest_vault.key();
+        round.end_timestamp = end_timestamp;
+        round.charity_count = 0;
+        round.finalized = false;
+
+        Ok(())
+    }
+
+    pub fn add_qf_charity(ctx: Context<AddQfCharity>) -> Result<()> {
+        authorize(
+            ctx.program_id,
+            &ctx.accounts.donation_pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_ADD_QF_CHARITY,
+                0,
+                &[ctx.accounts.qf_round.key(), ctx.accounts.donation_vault.key()],
+            ),
+        )?;
+        let round = &mut ctx.accounts.qf_round;
+
+        let count = round.charity_count as usize;
+        if count >= MAX_QF_CHARITIES {
+            return Err(ErrorCode::QfRoundFull.into());
+        }
+        if round.charities[..count].contains(&ctx.accounts.donation_vault.key()) {
+            return Err(ErrorCode::QfCharityAlreadyAdded.into());
+        }
+
+        round.charities[count] = ctx.accounts.donation_vault.key();
+        round.charity_count += 1;
+
+        Ok(())
+    }
+
+    /// Sets how much of the user's attributed donation backs `charity_index`
+    /// this round. Attributed donation allocated to a round stays used up.
+    pub fn allocate_qf(ctx: Context<AllocateQf>, charity_index: u8, amount: u64) -> Result<()> {
+        let round = &mut ctx.accounts.qf_round;
+        let user = &mut ctx.accounts.user;
+        let contribution = &mut ctx.accounts.contribution;
+        let index = charity_index as usize;
+
+        if round.finalized || ctx.accounts.clock.unix_timestamp >= round.end_timestamp {
+            return Err(ErrorCode::QfRoundEnded.into());
+        }
+        if index >= round.charity_count as usize {
+            return Err(ErrorCode::QfCharityNotFound.into());
+        }
+
+        user.settle_donation(&ctx.accounts.donation_pool, ctx.accounts.clock.unix_timestamp);
+        let allocated = contribution
+            .total
+            .checked_sub(contribution.amounts[index])
+            .unwrap()
+            .checked_add(amount)
+            .unwrap();
+        user.reallocate_qf(contribution.total, allocated)?;
+        round.reallocate(index, contribution.amounts[index], amount);
+
+        contribution.qf_round = round.key();
+        contribution.user = user.key();
+        contribution.amounts[index] = amount;
+        contribution.total = allocated;
+
+        Ok(())
+    }
+
+    /// Pays the round's donations to the charities in proportion to what was
+    /// allocated to them, the unallocated rest to the pool's charity, and splits
+    /// the matching pot by quadratic funding. Remaining accounts are the round's
+    /// charity donation vaults, in round order.
+    pub fn finalize_qf_round(ctx: Context<FinalizeQfRound>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let round = &mut ctx.accounts.qf_round;
+        let count = round.charity_count as usize;
+
+        if round.finalized {
+            return Err(ErrorCode::QfRoundFinalized.into());
+        }
+        if ctx.accounts.clock.unix_timestamp < round.end_timestamp {
+            return Err(ErrorCode::QfRoundNotEnded.into());
+        }
+        if ctx.remaining_accounts.len() != count {
+            return Err(ErrorCode::QfCharityNotFound.into());
+        }
+
+        round.finalized = true;
+        pool.qf_round = Pubkey::default();
+        pool.qf_vault = pool.donation_vault;
+
+        // Donations collected during the round follow the allocations.
+        let interest = ctx.accounts.interest_vault.amount;
+        let mut interest_paid: u64 = 0;
+        for i in 0..count {
+            let donation_vault = &ctx.remaining_accounts[i];
+            if donation_vault.key() != round.charities[i] {
+                return Err(ErrorCode::QfCharityNotFound.into());
+            }
+
+            let amount = round.interest_share(i, interest);
+            round.donations[i] = amount;
+            if amount == 0 {
+                continue;
+            }
+            interest_paid = interest_paid.checked_add(amount).unwrap();
+
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.interest_vault.to_account_info(),
+                    to: donation_vault.clone(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, amount)?;
+        }
+
+        // Transfer the unallocated donations to the pool's charity.
+        let unallocated = interest.checked_sub(interest_paid).unwrap();
+        if unallocated > 0 {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.interest_vault.to_account_info(),
+                    to: ctx.accounts.donation_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, unallocated)?;
+        }
+
+        // Without any match weight the pot stays for `reclaim_qf_pot`.
+        round.allocations = round.match_allocations(ctx.accounts.matching_vault.amount);
+        for i in 0..count {
+            let donation_vault = &ctx.remaining_accounts[i];
+
+            let allocation = round.allocations[i];
+            if allocation == 0 {
+                continue;
+            }
+
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.matching_vault.to_account_info(),
+                    to: donation_vault.clone(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, allocation)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns what finalization left in the matching pot, all of it when no
+    /// charity earned a match, to the round's authority.
+    pub fn reclaim_qf_pot(ctx: Context<ReclaimQfPot>) -> Result<()> {
+        if !ctx.accounts.qf_round.finalized {
+            return Err(ErrorCode::QfRoundNotEnded.into());
+        }
+
+        let pool = &ctx.accounts.donation_pool;
+        let amount = ctx.accounts.matching_vault.amount;
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        // Return the matching pot to the authority.
+        {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.matching_vault.to_account_info(),
+                    to: ctx.accounts.receiving_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, amount)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the target share of deposits held in a secondary reserve. The rest
+    /// stays in the pool's primary reserve.
+    /// Increases take effect after `MIGRATION_NOTICE_PERIOD`, decreases at once.
+    pub fn set_reserve_position(ctx: Context<SetReservePosition>, target_weight_bps: u16) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let position = &mut ctx.accounts.position;
+        let now = ctx.accounts.clock.unix_timestamp;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            now,
+            CouncilAction::new(
+                COUNCIL_ACTION_SET_RESERVE_POSITION,
+                target_weight_bps as u64,
+                &[
+                    ctx.accounts.reserve.key(),
+                    ctx.accounts.collateral_mint.key(),
+                    ctx.accounts.collateral_vault.key(),
+                ],
+            ),
+        )?;
+
+        if reserve_liquidity_mint(&ctx.accounts.reserve)? != pool.token_mint {
+            return Err(ErrorCode::ReserveMintMismatch.into());
+        }
+
+        position.settle_weight(now);
+        let allocated_weight_bps = pool
+            .allocated_weight_bps
+            .checked_sub(position.requested_weight_bps())
+            .unwrap()
+            .checked_add(target_weight_bps)
+            .unwrap();
+        if allocated_weight_bps as u64 > MAX_ALLOCATED_WEIGHT_BPS {
+            return Err(ErrorCode::ReserveWeightsTooHigh.into());
+        }
+        pool.allocated_weight_bps = allocated_weight_bps;
+
+        if position.donation_pool == Pubkey::default() {
+            pool.position_count = pool.position_count.checked_add(1).unwrap();
+        }
+        position.donation_pool = pool.key();
+        position.reserve = ctx.accounts.reserve.key();
+        position.collateral_mint = ctx.accounts.collateral_mint.key();
+        position.collateral_vault = ctx.accounts.collateral_vault.key();
+        if target_weight_bps <= position.target_weight_bps {
+            position.target_weight_bps = target_weight_bps;
+            position.pending_weight_bps = 0;
+            position.weight_effective_after = 0;
+        } else {
+            position.pending_weight_bps = target_weight_bps;
+            position.weight_effective_after = now.checked_add(MIGRATION_NOTICE_PERIOD).unwrap();
+        }
+
+        Ok(())
+    }
+
+    /// Permissionless. Sweeps the position's interest into the primary reserve
+    /// and moves principal between the primary reserve and the position until
+    /// it is back at its target weight.
+    pub fn rebalance<'info>(ctx: Context<'_, '_, '_, 'info, Rebalance<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        let position = &mut ctx.accounts.position;
+        position.settle_weight(ctx.accounts.clock.unix_timestamp);
+
+        let target = pool
+            .total_deposited
+            .checked_mul(position.target_weight_bps as u128)
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR as u128)
+            .unwrap() as u64;
+        let drift = (target as i128) - (position.principal as i128);
+        let tolerance = pool
+            .total_deposited
+            .checked_mul(REBALANCE_DRIFT_BPS as u128)
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR as u128)
+            .unwrap() as i128;
+
+        let position_rate = exchange_rate(&ctx.accounts.position_reserve)?;
+        let position_value = position_rate.collateral_to_liquidity(ctx.accounts.collateral_vault.amount)?;
+        let surplus = position_value.saturating_sub(position.principal);
+        if drift.abs() <= tolerance && surplus == 0 {
+            return Err(ErrorCode::NoRebalanceNeeded.into());
+        }
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let (staking_accounts, _) = split_staking_accounts(pool, ctx.remaining_accounts)?;
+        let staking = CollateralStaking {
+            staking_accounts,
+            yield_token_vault: ctx.accounts.yield_token_vault.to_account_info(),
+            reserve: ctx.accounts.reserve.clone(),
+            lending_market: ctx.accounts.lending_market.clone(),
+            lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+            pool_signer: ctx.accounts.pool_signer.to_account_info(),
+            clock: ctx.accounts.clock.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            lending_program: ctx.accounts.lending_program.clone(),
+        };
+
+        // Redeem from the position: its interest, plus any excess principal.
+        let withdraw = if -drift > tolerance {
+            surplus.checked_add((-drift) as u64).unwrap()
+        } else {
+            surplus
+        };
+        if withdraw > 0 {
+            let collateral = std::cmp::min(
+                position_rate.liquidity_to_collateral(withdraw)?,
+                ctx.accounts.collateral_vault.amount,
+            );
+            let vault_before = ctx.accounts.token_vault.amount;
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.lending_program.clone(),
+                Redeem {
+                    source_collateral: ctx.accounts.collateral_vault.to_account_info(),
+                    destination_liquidity: ctx.accounts.token_vault.to_account_info(),
+                    reserve: ctx.accounts.position_reserve.clone(),
+                    reserve_liquidity_supply: ctx.accounts.position_reserve_liquidity_supply.clone(),
+                    reserve_collateral_mint: ctx.accounts.collateral_mint.to_account_info(),
+                    lending_market: ctx.accounts.position_lending_market.clone(),
+                    lending_market_authority: ctx.accounts.position_lending_market_authority.clone(),
+                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                },
+                pool_signer
+            );
+            redeem(cpi_ctx, collateral)?;
+
+            ctx.accounts.token_vault.reload()?;
+            let received = ctx.accounts.token_vault.amount.checked_sub(vault_before).unwrap();
+            let principal = received.saturating_sub(surplus);
+            position.principal = position.principal.saturating_sub(principal);
+            pool.allocated_principal = pool.allocated_principal.saturating_sub(principal as u128);
+        }
+
+        // Redeem principal from the primary reserve for an under-weight position,
+        // unstaking the primary collateral first.
+        if drift > tolerance {
+            staking.unstake_all(pool)?;
+            ctx.accounts.yield_token_vault.reload()?;
+            let collateral = std::cmp::min(
+                exchange_rate(&ctx.accounts.reserve)?.liquidity_to_collateral(drift as u64)?,
+                ctx.accounts.yield_token_vault.amount,
+            );
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.lending_program.clone(),
+                Redeem {
+                    source_collateral: ctx.accounts.yield_token_vault.to_account_info(),
+                    destination_liquidity: ctx.accounts.token_vault.to_account_info(),
+                    reserve: ctx.accounts.reserve.clone(),
+                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
+                    reserve_collateral_mint: ctx.accounts.yield_token_mint.to_account_info(),
+                    lending_market: ctx.accounts.lending_market.clone(),
+                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                },
+                pool_signer
+            );
+            redeem(cpi_ctx, collateral)?;
+
+            ctx.accounts.token_vault.reload()?;
+            let amount = ctx.accounts.token_vault.amount;
+
+            // deposit into the position's reserve
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.lending_program.clone(),
+                Deposit {
+                    source_liquidity: ctx.accounts.token_vault.to_account_info(),
+                    destination_collateral: ctx.accounts.collateral_vault.to_account_info(),
+                    reserve: ctx.accounts.position_reserve.clone(),
+                    reserve_liquidity_supply: ctx.accounts.position_reserve_liquidity_supply.clone(),
+                    reserve_collateral_mint: ctx.accounts.collateral_mint.to_account_info(),
+                    lending_market: ctx.accounts.position_lending_market.clone(),
+                    lending_market_authority: ctx.accounts.position_lending_market_authority.clone(),
+                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                },
+                pool_signer
+            );
+            deposit_reserve(cpi_ctx, amount)?;
+
+            position.principal = position.principal.checked_add(amount).unwrap();
+            pool.allocated_principal = pool.allocated_principal.checked_add(amount as u128).unwrap();
+        } else {
+            // Whatever came back from the position goes into the primary
+            // reserve, where its interest part is donated at the next harvest.
+            ctx.accounts.token_vault.reload()?;
+            let amount = ctx.accounts.token_vault.amount;
+            if amount > 0 {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.lending_program.clone(),
+                    Deposit {
+                        source_liquidity: ctx.accounts.token_vault.to_account_info(),
+                        destination_collateral: ctx.accounts.yield_token_vault.to_account_info(),
+                        reserve: ctx.accounts.reserve.clone(),
+                        reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
+                        reserve_collateral_mint: ctx.accounts.yield_token_mint.to_account_info(),
+                        lending_market: ctx.accounts.lending_market.clone(),
+                        lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+                        transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                        clock: ctx.accounts.clock.to_account_info(),
+                        token_program: ctx.accounts.token_program.to_account_info()
+                    },
+                    pool_signer
+                );
+                deposit_reserve(cpi_ctx, amount)?;
+            }
+        }
+
+        ctx.accounts.yield_token_vault.reload()?;
+        staking.stake(pool, ctx.accounts.yield_token_vault.amount)?;
+
+        Ok(())
+    }
+
+    /// Announces a move of the primary reserve position. Users may unstake
+    /// freely, locked stake included, until it is executed, cancelled or lapses.
+    pub fn propose_strategy_migration(ctx: Context<ProposeStrategyMigration>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_MIGRATE_STRATEGY,
+                0,
+                &[
+                    ctx.accounts.new_reserve.key(),
+                    ctx.accounts.new_yield_token_mint.key(),
+                    ctx.accounts.new_yield_token_vault.key(),
+                ],
+            ),
+        )?;
+        if reserve_liquidity_mint(&ctx.accounts.new_reserve)? != pool.token_mint {
+            return Err(ErrorCode::ReserveMintMismatch.into());
+        }
+        pool.pending_reserve = ctx.accounts.new_reserve.key();
+        pool.pending_yield_token_mint = ctx.accounts.new_yield_token_mint.key();
+        pool.pending_yield_token_vault = ctx.accounts.new_yield_token_vault.key();
+        pool.migration_execute_after = ctx
+            .accounts
+            .clock
+            .unix_timestamp
+            .checked_add(MIGRATION_NOTICE_PERIOD)
+            .unwrap();
+
+        Ok(())
+    }
+
+    pub fn cancel_strategy_migration(ctx: Context<CancelStrategyMigration>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.migration_execute_after == 0 {
+            return Err(ErrorCode::NoMigrationPending.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_CANCEL_MIGRATION, 0, &[pool.pending_reserve]),
+        )?;
+        pool.clear_migration();
+
+        Ok(())
+    }
+
+    /// Pools with reward staking unstake everything from Port Fi. first and
+    /// need reward staking set up again for the new reserve. Their staking
+    /// accounts lead the remaining accounts, followed by the council's.
+    pub fn execute_strategy_migration<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteStrategyMigration<'info>>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.migration_execute_after == 0 {
+            return Err(ErrorCode::NoMigrationPending.into());
+        }
+        if ctx.accounts.clock.unix_timestamp < pool.migration_execute_after {
+            return Err(ErrorCode::MigrationNoticePeriod.into());
+        }
+        if !pool.migration_pending(ctx.accounts.clock.unix_timestamp) {
+            return Err(ErrorCode::MigrationExpired.into());
+        }
+        let (staking_accounts, council_accounts) = split_staking_accounts(pool, ctx.remaining_accounts)?;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            council_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_EXECUTE_MIGRATION, 0, &[pool.pending_reserve]),
+        )?;
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        // unstake everything from Port Fi. staking
+        CollateralStaking {
+            staking_accounts,
+            yield_token_vault: ctx.accounts.yield_token_vault.to_account_info(),
+            reserve: ctx.accounts.reserve.clone(),
+            lending_market: ctx.accounts.lending_market.clone(),
+            lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+            pool_signer: ctx.accounts.pool_signer.to_account_info(),
+            clock: ctx.accounts.clock.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            lending_program: ctx.accounts.lending_program.clone(),
+        }
+        .unstake_all(pool)?;
+        ctx.accounts.yield_token_vault.reload()?;
+
+        // withdraw everything from the old reserve
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.lending_program.clone(),
+                Redeem {
+                    source_collateral: ctx.accounts.yield_token_vault.to_account_info(),
+                    destination_liquidity: ctx.accounts.token_vault.to_account_info(),
+                    reserve: ctx.accounts.reserve.clone(),
+                    reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
+                    reserve_collateral_mint: ctx.accounts.yield_token_mint.to_account_info(),
+                    lending_market: ctx.accounts.lending_market.clone(),
+                    lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                },
+                pool_signer
+            );
+            redeem(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+        }
+
+        ctx.accounts.token_vault.reload()?;
+
+        // deposit into the new reserve
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.lending_program.clone(),
+                Deposit {
+                    source_liquidity: ctx.accounts.token_vault.to_account_info(),
+                    destination_collateral: ctx.accounts.new_yield_token_vault.to_account_info(),
+                    reserve: ctx.accounts.new_reserve.clone(),
+                    reserve_liquidity_supply: ctx.accounts.new_reserve_liquidity_supply.clone(),
+                    reserve_collateral_mint: ctx.accounts.new_yield_token_mint.to_account_info(),
+                    lending_market: ctx.accounts.new_lending_market.clone(),
+                    lending_market_authority: ctx.accounts.new_lending_market_authority.clone(),
+                    transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                },
+                pool_signer
+            );
+            deposit_reserve(cpi_ctx, ctx.accounts.token_vault.amount)?;
+        }
+
+        pool.reserve = pool.pending_reserve;
+        pool.yield_token_mint = pool.pending_yield_token_mint;
+        pool.yield_token_vault = pool.pending_yield_token_vault;
+        pool.clear_migration();
+        pool.port_stake_account = Pubkey::default();
+        pool.port_obligation = Pubkey::default();
+        pool.port_staking_pool = Pubkey::default();
+
+        Ok(())
+    }
+
+    /// Registers the pool's Port obligation and staking account, both owned by
+    /// the pool signer, and where claimed rewards go. Primary collateral is
+    /// staked through the obligation from then on. Rewards are kept in
+    /// `reward_vault` unless a charity vault for the reward mint is given.
+    pub fn set_reward_staking(ctx: Context<SetRewardStaking>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.staked_collateral != 0 {
+            return Err(ErrorCode::CollateralStaked.into());
+        }
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_SET_REWARD_STAKING,
+                0,
+                &[
+                    ctx.accounts.port_obligation.key(),
+                    ctx.accounts.port_stake_account.key(),
+                    ctx.accounts.staking_pool.key(),
+                    ctx.accounts.reward_vault.key(),
+                    ctx.accounts.reward_donation_vault.key(),
+                ],
+            ),
+        )?;
+        pool.port_stake_account = ctx.accounts.port_stake_account.key();
+        pool.port_obligation = ctx.accounts.port_obligation.key();
+        pool.port_staking_pool = ctx.accounts.staking_pool.key();
+        pool.reward_vault = ctx.accounts.reward_vault.key();
+        pool.reward_donation_vault = ctx.accounts.reward_donation_vault.key();
+
+        Ok(())
+    }
+
+    /// Permissionless. Claims PORT liquidity mining rewards and forwards them
+    /// to the charity.
+    pub fn harvest_rewards(ctx: Context<HarvestRewards>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        // claim from Port Finance staking
+        let vault_before = ctx.accounts.reward_vault.amount;
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.staking_program.clone(),
+                ClaimReward {
+                    stake_account_owner: ctx.accounts.pool_signer.to_account_info(),
+                    stake_account: ctx.accounts.port_stake_account.clone(),
+                    staking_pool: ctx.accounts.staking_pool.clone(),
+                    reward_token_pool: ctx.accounts.reward_token_pool.clone(),
+                    reward_dest: ctx.accounts.reward_vault.to_account_info(),
+                    staking_program_authority: ctx.accounts.staking_program_authority.clone(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+                pool_signer
+            );
+            claim_reward(cpi_ctx)?;
+        }
+
+        ctx.accounts.reward_vault.reload()?;
+        let rewards = ctx.accounts.reward_vault.amount.checked_sub(vault_before).unwrap();
+        if rewards == 0 || pool.reward_donation_vault == pool.reward_vault {
+            return Ok(());
+        }
+
+        // Forward the rewards to the charity.
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.reward_donation_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, rewards)?;
+        }
+
+        pool.total_rewards_donated = pool.total_rewards_donated.checked_add(rewards as u128).unwrap();
+
+        Ok(())
+    }
+
+    /// Pays the charity in `payout_mint`. Harvests escrow the donation in
+    /// `payout_vault`, from where `swap_payout` swaps it through the given SPL
+    /// Token Swap pool, accepting up to `slippage_bps` below the oracle price.
+    /// Needs the pool's price feed. Not compatible with streaming.
+    pub fn set_payout(ctx: Context<SetPayout>, slippage_bps: u16) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        if pool.stream_duration != 0 {
+            return Err(ErrorCode::PayoutWithStream.into());
+        }
+        if pool.payout_vault != ctx.accounts.payout_vault.key() && pool.payout_pending > 0 {
+            return Err(ErrorCode::PayoutPending.into());
+        }
+        if pool.price_feed == Pubkey::default() {
+            return Err(ErrorCode::PriceFeedRequired.into());
+        }
+        if slippage_bps > MAX_PAYOUT_SLIPPAGE_BPS {
+            return Err(ErrorCode::SlippageTooHigh.into());
+        }
+        pyth_price(&ctx.accounts.payout_price_feed, ctx.accounts.clock.unix_timestamp)?;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_SET_PAYOUT,
+                slippage_bps as u64,
+                &[
+                    ctx.accounts.payout_mint.key(),
+                    ctx.accounts.payout_donation_vault.key(),
+                    ctx.accounts.swap_pool.key(),
+                    ctx.accounts.payout_price_feed.key(),
+                    ctx.accounts.payout_vault.key(),
+                ],
+            ),
+        )?;
+
+        pool.payout_mint = ctx.accounts.payout_mint.key();
+        pool.payout_decimals = ctx.accounts.payout_mint.decimals;
+        pool.payout_donation_vault = ctx.accounts.payout_donation_vault.key();
+        pool.swap_pool = ctx.accounts.swap_pool.key();
+        pool.payout_price_feed = ctx.accounts.payout_price_feed.key();
+        pool.payout_slippage_bps = slippage_bps;
+        pool.payout_vault = ctx.accounts.payout_vault.key();
+
+        Ok(())
+    }
+
+    /// Permissionless. Swaps the donations escrowed in `payout_vault` for no
+    /// less than their oracle value, less the configured slippage. While either
+    /// price is unusable the charity is paid in `token_mint` instead.
+    pub fn swap_payout(ctx: Context<SwapPayout>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+        let amount = pool.payout_pending;
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        let now = ctx.accounts.clock.unix_timestamp;
+        let minimum_amount_out = match (
+            pyth_price(&ctx.accounts.price_feed, now),
+            pyth_price(&ctx.accounts.payout_price_feed, now),
+        ) {
+            (Ok(price), Ok(payout_price)) => payout_minimum_out(
+                amount,
+                ctx.accounts.token_mint.decimals,
+                price,
+                pool.payout_decimals,
+                payout_price,
+                pool.payout_slippage_bps,
+            ),
+            _ => None,
+        };
+
+        if let Some(minimum_amount_out) = minimum_amount_out {
+            swap_to_payout(ctx.accounts, amount, minimum_amount_out)?;
+        } else {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.payout_vault.to_account_info(),
+                    to: ctx.accounts.donation_vault.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, amount)?;
+        }
+
+        ctx.accounts.donation_pool.payout_pending = 0;
+
+        Ok(())
+    }
+
+    /// Values future harvests in USD with the given Pyth price account. Passing
+    /// the default pubkey (the system program) turns pricing off.
+    pub fn set_price_feed(ctx: Context<SetPriceFeed>) -> Result<()> {
+        let price_feed = &ctx.accounts.price_feed;
+        if price_feed.key() != Pubkey::default() {
+            pyth_price(price_feed, ctx.accounts.clock.unix_timestamp)?;
+        }
+        authorize(
+            ctx.program_id,
+            &ctx.accounts.donation_pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_SET_PRICE_FEED, 0, &[price_feed.key()]),
+        )?;
+
+        ctx.accounts.donation_pool.price_feed = price_feed.key();
+
+        Ok(())
+    }
+
+    /// Pays future donations to a new charity. The rest of the current stream
+    /// and any donations waiting for a payout swap go to the outgoing charity
+    /// first, in `token_mint`.
+    pub fn set_charity(ctx: Context<SetCharity>) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(
+                COUNCIL_ACTION_SET_CHARITY,
+                0,
+                &[ctx.accounts.new_donation_address.key(), ctx.accounts.new_donation_vault.key()],
+            ),
+        )?;
+
+        flush_stream(
+            pool,
+            &ctx.accounts.stream_vault,
+            &ctx.accounts.donation_vault,
+            &ctx.accounts.pool_signer,
+            &ctx.accounts.token_program,
+        )?;
+        flush_payout(
+            pool,
+            &ctx.accounts.payout_vault,
+            &ctx.accounts.donation_vault,
+            &ctx.accounts.pool_signer,
+            &ctx.accounts.token_program,
+        )?;
+
+        pool.change_charity(ctx.accounts.new_donation_address.key(), ctx.accounts.new_donation_vault.key());
+
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let pool = &mut ctx.accounts.donation_pool;
+        authorize(
+            ctx.program_id,
+            pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_SET_PAUSED, paused as u64, &[]),
+        )?;
+        pool.paused = paused;
+
+        Ok(())
+    }
+
+    /// Hands every sensitive pool setting to an M-of-N council: once created,
+    /// the setters only apply changes the council approved (see `authorize`).
+    /// The authority itself may still be an external multisig.
+    pub fn create_council(ctx: Context<CreateCouncil>, members: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        let council = &mut ctx.accounts.council;
+        council.donation_pool = ctx.accounts.donation_pool.key();
+        council.set_members(&members, threshold)?;
+
+        ctx.accounts.donation_pool.council = council.key();
+
+        Ok(())
+    }
+
+    /// Replaces the council's members and threshold. Proposals made before the
+    /// change can no longer be approved or executed.
+    pub fn set_council(ctx: Context<SetCouncil>, members: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        authorize(
+            ctx.program_id,
+            &ctx.accounts.donation_pool,
+            ctx.accounts.authority.key(),
+            ctx.remaining_accounts,
+            ctx.accounts.clock.unix_timestamp,
+            CouncilAction::new(COUNCIL_ACTION_SET_COUNCIL, threshold as u64, &[Council::members_key(&members)]),
+        )?;
+
+        let council = &mut ctx.accounts.council;
+        council.set_members(&members, threshold)?;
+        council.min_proposal_index = council.proposal_count;
+
+        Ok(())
+    }
+
+    /// A member proposes `action` with `value` and `keys`, counting as its
+    /// first approval. Once approved, the action's setter applies it when
+    /// called with exactly the proposed arguments and accounts, before
+    /// `COUNCIL_PROPOSAL_TTL` runs out.
+    pub fn propose_council_action(
+        ctx: Context<ProposeCouncilAction>,
+        action: u8,
+        value: u64,
+        keys: [Pubkey; COUNCIL_PROPOSAL_KEYS],
+    ) -> Result<()> {
+        if action > COUNCIL_ACTION_SET_COUNCIL {
+            return Err(ErrorCode::InvalidCouncilAction.into());
+        }
+        let council = &mut ctx.accounts.council;
+        let member = council
+            .member_index(ctx.accounts.member.key)
+            .ok_or(ErrorCode::NotCouncilMember)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.council = council.key();
+        proposal.index = council.proposal_count;
+        proposal.action = action;
+        proposal.value = value;
+        proposal.keys = keys;
+        proposal.approvals = 1 << member;
+        proposal.approval_count = 1;
+        proposal.expires_at = ctx
+            .accounts
+            .clock
+            .unix_timestamp
+            .checked_add(COUNCIL_PROPOSAL_TTL)
+            .unwrap();
+
+        council.proposal_count = council.proposal_count.checked_add(1).unwrap();
+
+        Ok(())
+    }
+
+    pub fn approve_council_action(ctx: Context<ApproveCouncilAction>) -> Result<()> {
+        let member = ctx
+            .accounts
+            .council
+            .member_index(ctx.accounts.member.key)
+            .ok_or(ErrorCode::NotCouncilMember)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        if proposal.executed {
+            return Err(ErrorCode::CouncilActionExecuted.into());
+        }
+        if ctx.accounts.clock.unix_timestamp >= proposal.expires_at
+            || proposal.index < ctx.accounts.council.min_proposal_index
+        {
+            return Err(ErrorCode::CouncilActionExpired.into());
+        }
+        if proposal.approvals & (1 << member) != 0 {
+            return Err(ErrorCode::AlreadyApproved.into());
+        }
+        proposal.approvals |= 1 << member;
+        proposal.approval_count = proposal.approval_count.checked_add(1).unwrap();
+
+        Ok(())
+    }
+
+    pub fn create_harvest_history(ctx: Context<CreateHarvestHistory>) -> Result<()> {
+        let history = &mut ctx.accounts.harvest_history.load_init()?;
+        history.donation_pool = ctx.accounts.donation_pool.key();
+
+        Ok(())
+    }
+
+    pub fn create_campaign(ctx: Context<CreateCampaign>) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.authority = ctx.accounts.authority.key();
+        campaign.donation_wallet = ctx.accounts.donation_address.key();
+        campaign.pool_count = 0;
+
+        Ok(())
+    }
+
+    pub fn add_campaign_pool(ctx: Context<AddCampaignPool>) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        let pool = &ctx.accounts.donation_pool;
+
+        let count = campaign.pool_count as usize;
+        if count >= MAX_CAMPAIGN_POOLS {
+            return Err(ErrorCode::CampaignFull.into());
+        }
+        if campaign.pools[..count].contains(&pool.key()) {
+            return Err(ErrorCode::PoolAlreadyInCampaign.into());
+        }
+
+        campaign.pools[count] = pool.key();
+        campaign.token_mints[count] = pool.token_mint;
+        campaign.total_donated[count] = 0;
+        campaign.pool_count += 1;
+
+        Ok(())
+    }
+
+    /// Harvests every pool in the campaign. Remaining accounts hold one
+    /// `TransferInterestToCharity` account list per member pool, in campaign order,
+    /// each followed by the pool's matching pairs.
+    pub fn harvest_campaign<'info>(ctx: Context<'_, '_, '_, 'info, HarvestCampaign<'info>>) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        let mut remaining: &[AccountInfo<'info>] = ctx.remaining_accounts;
+
+        for i in 0..campaign.pool_count as usize {
+            let mut accounts = TransferInterestToCharity::try_accounts(ctx.program_id, &mut remaining, &[])?;
+            if accounts.donation_pool.key() != campaign.pools[i] {
+                return Err(ErrorCode::CampaignPoolMismatch.into());
+            }
+            // A pool whose charity changed is still harvested for its new
+            // charity, but no longer counts towards the campaign. Neither do
+            // donations collected for a quadratic funding round.
+            let credited = accounts.donation_pool.donation_wallet == campaign.donation_wallet
+                && accounts.donation_pool.qf_round == Pubkey::default();
+
+            // The pool's staking accounts and matching pairs follow its
+            // accounts, as for `transfer_interest_to_charity`.
+            let (staking_accounts, rest) = split_staking_accounts(&accounts.donation_pool, remaining)?;
+            remaining = rest;
+            let matching_len = 2 * accounts.donation_pool.matching_pool_count as usize;
+            if remaining.len() < matching_len {
+                return Err(ErrorCode::MatchingPoolMismatch.into());
+            }
+            let (matching_accounts, rest) = remaining.split_at(matching_len);
+            remaining = rest;
+
+            let interest = harvest_interest(
+                ctx.program_id,
+                &mut accounts,
+                staking_accounts,
+                matching_accounts,
+            )?;
+            if credited {
+                campaign.total_donated[i] = campaign.total_donated[i].checked_add(interest as u128).unwrap();
+            }
+
+            accounts.exit(ctx.program_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read-only health probe, meant to be run through `simulateTransaction`.
+    /// Remaining accounts hold a `[position, collateral_vault, reserve]` triple
+    /// for every secondary reserve position of the pool. Staked collateral is
+    /// counted at the pool's `staked_collateral`.
+    pub fn check_invariants(ctx: Context<CheckInvariants>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+
+        let collateral = ctx.accounts.yield_token_vault.amount.checked_add(pool.staked_collateral).unwrap();
+        let value = exchange_rate(&ctx.accounts.reserve)?.collateral_to_liquidity(collateral)?;
+        if (value as u128) < pool.primary_deposits() {
+            return Err(ErrorCode::CollateralValueBelowDeposits.into());
+        }
+        let positions_value = positions_value(pool, ctx.remaining_accounts)?;
+        if (value as u128).checked_add(positions_value).unwrap() < pool.total_deposited {
+            return Err(ErrorCode::CollateralValueBelowDeposits.into());
+        }
+        if ctx.accounts.token_vault.amount != 0 {
+            return Err(ErrorCode::UnexpectedIdleFunds.into());
+        }
+        if pool.total_deposited > 0 && pool.user_stake_count == 0 {
+            return Err(ErrorCode::UserCountInconsistent.into());
+        }
+
+        Ok(())
+    }
+
+    /// Sets `PoolStats` as return data, meant to be run through `simulateTransaction`.
+    /// Remaining accounts are as for `check_invariants`.
+    pub fn get_pool_stats(ctx: Context<GetPoolStats>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+
+        let collateral = ctx.accounts.yield_token_vault.amount.checked_add(pool.staked_collateral).unwrap();
+        let underlying_value = (exchange_rate(&ctx.accounts.reserve)?.collateral_to_liquidity(collateral)? as u128)
+            .checked_add(positions_value(pool, ctx.remaining_accounts)?)
+            .unwrap();
+        let pending_interest = underlying_value.saturating_sub(pool.total_deposited) as u64;
+        let underlying_value = underlying_value as u64;
+
+        // Annualise the interest accrued since the last harvest.
+        let elapsed = ctx
+            .accounts
+            .clock
+            .unix_timestamp
+            .saturating_sub(pool.last_harvest_timestamp) as u128;
+        let estimated_apy_bps = if elapsed == 0 || pool.total_deposited == 0 {
+            0
+        } else {
+            (pending_interest as u128)
+                .checked_mul(BPS_DENOMINATOR as u128 * SECONDS_PER_YEAR)
+                .unwrap()
+                .checked_div(pool.total_deposited.checked_mul(elapsed).unwrap())
+                .unwrap() as u64
+        };
+
+        let stats = PoolStats {
+            underlying_value,
+            pending_interest,
+            total_donated: pool.total_donated,
+            total_donated_usd: pool.total_donated_usd,
+            user_count: pool.user_stake_count,
+            estimated_apy_bps,
+        };
+        set_return_data(&stats.try_to_vec()?);
+
+        Ok(())
+    }
+
+    /// Mints a receipt for the donations attributed to the user since their
+    /// last receipt. The receipt names the pool's charity at mint time and
+    /// values the amount at the pool's average donation price; it does not
+    /// track which charity, or at what price, each harvest was donated.
+    pub fn mint_receipt(ctx: Context<MintReceipt>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+        let user = &mut ctx.accounts.user;
+
+        if pool.harvest_count == 0 {
+            return Err(ErrorCode::NoHarvestToReceipt.into());
+        }
+
+        // Donations attributed to the user since their last receipt.
+        user.settle_donation(pool, ctx.accounts.clock.unix_timestamp);
+        let amount = user
+            .attributed_donation
+            .checked_sub(user.receipted_donation)
+            .unwrap();
+        if amount == 0 {
+            return Err(ErrorCode::ReceiptAlreadyMinted.into());
+        }
+
+        user.receipted_donation = user.attributed_donation;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.donation_pool = pool.key();
+        receipt.owner = user.owner;
+        receipt.donation_wallet = pool.donation_wallet;
+        receipt.receipt_mint = ctx.accounts.receipt_mint.key();
+        receipt.period = pool.harvest_count;
+        receipt.amount = amount;
+        receipt.amount_usd = pool.average_usd_value(amount);
+        receipt.timestamp = ctx.accounts.clock.unix_timestamp;
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        // Mint the receipt token to the user.
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    to: ctx.accounts.receipt_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::mint_to(cpi_ctx, 1)?;
+        }
+
+        // Drop the mint authority so the supply is fixed at one.
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::SetAuthority {
+                    account_or_mint: ctx.accounts.receipt_mint.to_account_info(),
+                    current_authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::set_authority(cpi_ctx, AuthorityType::MintTokens, None)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn claim_badge(ctx: Context<ClaimBadge>) -> Result<()> {
+        let pool = &ctx.accounts.donation_pool;
+        let user = &mut ctx.accounts.user;
+
+        user.settle_donation(pool, Clock::get()?.unix_timestamp);
+        let tier = badge_tier(user.lifetime_donated_attributed, ctx.accounts.token_mint.decimals);
+        if tier <= user.badge_tier {
+            return Err(ErrorCode::BadgeTierNotReached.into());
+        }
+        let upgrade = (tier - user.badge_tier) as u64;
+        user.badge_tier = tier;
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        // Thaw an existing badge so it can be upgraded.
+        if ctx.accounts.badge_token_account.is_frozen() {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::ThawAccount {
+                    account: ctx.accounts.badge_token_account.to_account_info(),
+                    mint: ctx.accounts.badge_mint.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::thaw_account(cpi_ctx)?;
+        }
+
+        // One badge token per tier reached.
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.badge_mint.to_account_info(),
+                    to: ctx.accounts.badge_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::mint_to(cpi_ctx, upgrade)?;
+        }
+
+        // Freeze the badge so it can't be transferred.
+        {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::FreezeAccount {
+                    account: ctx.accounts.badge_token_account.to_account_info(),
+                    mint: ctx.accounts.badge_mint.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::freeze_account(cpi_ctx)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Stakes `amount` for the user, optionally as locked stake.
+pub fn stake_tokens<'info>(ctx: Context<'_, '_, '_, 'info, Stake<'info>>, amount: u64, locked: bool) -> Result<()> {
+    if amount == 0 {
+        return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+    }
+    if ctx.accounts.donation_pool.paused {
+        return Err(ErrorCode::PoolPaused.into());
+    }
+
+    // Transfer tokens into the stake vault, measuring what actually arrived.
+    let vault_before = ctx.accounts.token_vault.amount;
+    {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: ctx.accounts.stake_from_account.to_account_info(),
+                to: ctx.accounts.token_vault.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+            },
+        );
+        token::transfer(cpi_ctx, amount)?;
+    }
+
+    ctx.accounts.token_vault.reload()?;
+    let received = ctx
+        .accounts
+        .token_vault
+        .amount
+        .checked_sub(vault_before)
+        .unwrap();
+    if received == 0 {
+        return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+    } if received > amount {
+        return Err(ErrorCode::StakeAmountMismatch.into());
+    }
+
+    let pool = &mut ctx.accounts.donation_pool;
+
+    // Interest earned so far belongs to the stake that earned it.
+    let collateral = ctx.accounts.yield_token_vault.amount.checked_add(pool.staked_collateral).unwrap();
+    pool.accrue_interest(exchange_rate(&ctx.accounts.reserve)?.collateral_to_liquidity(collateral)?);
+
+    ctx.accounts.user.settle_donation(pool, ctx.accounts.clock.unix_timestamp);
+    ctx.accounts.user.accrue_draw_weight(pool, ctx.accounts.clock.unix_timestamp);
+    ctx.accounts.user.balance_staked = ctx
+        .accounts
+        .user
+        .balance_staked
+        .checked_add(received)
+        .unwrap();
+    ctx.accounts.user.reset_interest_debt(pool);
+    if locked {
+        ctx.accounts.user.locked_balance = ctx
+            .accounts
+            .user
+            .locked_balance
+            .checked_add(received)
+            .unwrap();
+    }
+
+    pool.total_deposited = pool.total_deposited.checked_add(received as u128).unwrap();
+
+    // deposit into Port Finance
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.lending_program.clone(),
+            Deposit {
+                source_liquidity: ctx.accounts.token_vault.to_account_info(),
+                destination_collateral: ctx.accounts.yield_token_vault.to_account_info(),
+                reserve: ctx.accounts.reserve.clone(),
+                reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
+                reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
+                lending_market: ctx.accounts.lending_market.clone(),
+                lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+                transfer_authority: ctx.accounts.transfer_authority.clone(),
+                clock: ctx.accounts.clock.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info()
+            },
+            pool_signer
+        );
+        deposit_reserve(cpi_ctx, received)?;
+    }
+
+    // Stake the new collateral for Port Fi. rewards.
+    let (staking_accounts, _) = split_staking_accounts(pool, ctx.remaining_accounts)?;
+    ctx.accounts.yield_token_vault.reload()?;
+    CollateralStaking {
+        staking_accounts,
+        yield_token_vault: ctx.accounts.yield_token_vault.to_account_info(),
+        reserve: ctx.accounts.reserve.clone(),
+        lending_market: ctx.accounts.lending_market.clone(),
+        lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+        pool_signer: ctx.accounts.pool_signer.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        lending_program: ctx.accounts.lending_program.clone(),
+    }
+    .stake(pool, ctx.accounts.yield_token_vault.amount)?;
+
+    Ok(())
+}
+
+/// Unstakes `amount` for the user and pays it into `receiving_vault`.
+pub fn unstake_tokens<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>, amount: u64) -> Result<()> {
+    if amount == 0 {
+        return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+    }
+
+    // Matured locked stake is redeemed first, the rest must be unlocked stake.
+    // Locks don't apply while a strategy migration is pending.
+    let now = ctx.accounts.clock.unix_timestamp;
+    let unlocked = if now >= ctx.accounts.user.lock_until
+        || ctx.accounts.donation_pool.migration_pending(now)
+    {
+        ctx.accounts.user.locked_balance
+    } else {
+        0
+    };
+    if amount > ctx.accounts.user.balance_staked {
+        return Err(ErrorCode::InsufficientFundUnstake.into());
+    }
+    let from_lock = std::cmp::min(amount, unlocked);
+    let free = ctx
+        .accounts
+        .user
+        .balance_staked
+        .saturating_sub(ctx.accounts.user.locked_balance);
+    if amount - from_lock > free {
+        return Err(ErrorCode::StakeLocked.into());
+    }
+
+    let pool = &mut ctx.accounts.donation_pool;
+
+    // Interest earned so far belongs to the stake that earned it.
+    let collateral = ctx.accounts.yield_token_vault.amount.checked_add(pool.staked_collateral).unwrap();
+    pool.accrue_interest(exchange_rate(&ctx.accounts.reserve)?.collateral_to_liquidity(collateral)?);
+
+    ctx.accounts.user.settle_donation(pool, ctx.accounts.clock.unix_timestamp);
+    ctx.accounts.user.accrue_draw_weight(pool, ctx.accounts.clock.unix_timestamp);
+    ctx.accounts.user.balance_staked = ctx
+        .accounts
+        .user
+        .balance_staked
+        .checked_sub(amount)
+        .unwrap();
+    // Locked stake never exceeds what's left staked.
+    ctx.accounts.user.locked_balance = std::cmp::min(
+        ctx.accounts.user.locked_balance.checked_sub(from_lock).unwrap(),
+        ctx.accounts.user.balance_staked,
+    );
+    ctx.accounts.user.reset_interest_debt(pool);
+
+    pool.total_deposited = pool.total_deposited.checked_sub(amount as u128).unwrap(); 
+
+    // Stake that voted while the epoch runs takes its votes with it, so it
+    // can't vote twice.
+    let (staking_accounts, remaining) = split_staking_accounts(pool, ctx.remaining_accounts)?;
+    let governance_epoch = pool.governance_epoch;
+    let position_accounts = if governance_epoch != 0
+        && ctx.accounts.user.voted_epoch == governance_epoch
+        && now < pool.epoch_end_timestamp
+    {
+        if remaining.len() < 2 {
+            return Err(ErrorCode::VoteAccountsMissing.into());
+        }
+        let (vote_accounts, rest) = remaining.split_at(2);
+        withdraw_vote(ctx.program_id, pool, &ctx.accounts.user, vote_accounts, now)?;
+        rest
+    } else {
+        remaining
+    };
+
+    // Unstake the collateral from Port Fi. so it can be redeemed.
+    let staking = CollateralStaking {
+        staking_accounts,
+        yield_token_vault: ctx.accounts.yield_token_vault.to_account_info(),
+        reserve: ctx.accounts.reserve.clone(),
+        lending_market: ctx.accounts.lending_market.clone(),
+        lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+        pool_signer: ctx.accounts.pool_signer.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        lending_program: ctx.accounts.lending_program.clone(),
+    };
+    staking.unstake_all(pool)?;
+    ctx.accounts.yield_token_vault.reload()?;
+
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::Approve {
+                to: ctx.accounts.yield_token_vault.to_account_info(),
+                delegate: ctx.accounts.transfer_authority.to_account_info(),
+                authority: ctx.accounts.pool_signer.to_account_info(), //todo use user account as signer
+            },
+            pool_signer
+        );
+        token::approve(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+    }
+
+    // withdraw from Port Finance to user's balance
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.lending_program.clone(),
+            Redeem {
+                source_collateral: ctx.accounts.yield_token_vault.to_account_info(),
+                destination_liquidity: ctx.accounts.token_vault.to_account_info(),
+                reserve: ctx.accounts.reserve.clone(),
+                reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
+                reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
+                lending_market: ctx.accounts.lending_market.clone(),
+                lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+                transfer_authority: ctx.accounts.pool_signer.to_account_info(),
+                clock: ctx.accounts.clock.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info()
+            },
+            pool_signer
+        );
+        redeem(cpi_ctx, ctx.accounts.yield_token_vault.amount)?;
+    }
+
+    // Deposits placed in secondary positions make up what the primary reserve
+    // is short of.
+    ctx.accounts.token_vault.reload()?;
+    if ctx.accounts.token_vault.amount < amount {
+        let shortfall = amount - ctx.accounts.token_vault.amount;
+        redeem_from_positions(ctx.program_id, ctx.accounts, position_accounts, shortfall)?;
+    }
+    let pool = &mut ctx.accounts.donation_pool;
+
+    // Transfer tokens into the user's personal token vault.
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: ctx.accounts.token_vault.to_account_info(),
+                to: ctx.accounts.receiving_vault.to_account_info(),
+                authority: ctx.accounts.pool_signer.to_account_info(), //todo use user account as signer
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, amount)?;
+    }
+
+    ctx.accounts.token_vault.reload()?;
+
+    // deposit into Port Finance
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.lending_program.clone(),
+            Deposit {
+                source_liquidity: ctx.accounts.token_vault.to_account_info(),
+                destination_collateral: ctx.accounts.yield_token_vault.to_account_info(),
+                reserve: ctx.accounts.reserve.clone(),
+                reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
+                reserve_collateral_mint: ctx.accounts.token_mint.to_account_info(),
+                lending_market: ctx.accounts.lending_market.clone(),
+                lending_market_authority: ctx.accounts.lending_market_authority.clone(),
+                transfer_authority: ctx.accounts.transfer_authority.clone(),
+                clock: ctx.accounts.clock.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info()
+            },
+            pool_signer
+        );
+        deposit_reserve(cpi_ctx, ctx.accounts.token_vault.amount)?;
+    }
+
+    ctx.accounts.yield_token_vault.reload()?;
+    staking.stake(pool, ctx.accounts.yield_token_vault.amount)?;
+
+    Ok(())
+}
+
+/// Creates the temporary WSOL account at `wsol_account`, owned by `owner` and
+/// holding `amount` wrapped lamports. Lamports already at the address count
+/// towards the balance, so prefunding the PDA can't block the instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn create_wsol_account<'info>(
+    owner: &Signer<'info>,
+    wsol_account: &UncheckedAccount<'info>,
+    token_mint: &Account<'info, Mint>,
+    system_program: &Program<'info, System>,
+    token_program: &Program<'info, Token>,
+    rent: &Sysvar<'info, Rent>,
+    wsol_seeds: &[&[u8]],
+    amount: u64,
+) -> Result<()> {
+    let required = rent
+        .minimum_balance(spl_token::state::Account::LEN)
+        .checked_add(amount)
+        .unwrap();
+    let shortfall = required.saturating_sub(wsol_account.lamports());
+    if shortfall > 0 {
+        let ix = system_instruction::transfer(owner.key, wsol_account.key, shortfall);
+        invoke(
+            &ix,
+            &[
+                owner.to_account_info(),
+                wsol_account.to_account_info(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    let ix = system_instruction::allocate(wsol_account.key, spl_token::state::Account::LEN as u64);
+    invoke_signed(
+        &ix,
+        &[wsol_account.to_account_info(), system_program.to_account_info()],
+        &[wsol_seeds],
+    )?;
+    let ix = system_instruction::assign(wsol_account.key, &spl_token::ID);
+    invoke_signed(
+        &ix,
+        &[wsol_account.to_account_info(), system_program.to_account_info()],
+        &[wsol_seeds],
+    )?;
+
+    let cpi_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        token::InitializeAccount {
+            account: wsol_account.to_account_info(),
+            mint: token_mint.to_account_info(),
+            authority: owner.to_account_info(),
+            rent: rent.to_account_info(),
+        },
+    );
+    token::initialize_account(cpi_ctx)
+}
+
+/// Redeems the pool's collateral, donates the accrued interest net of the
+/// protocol fee and lottery prize together with the sponsors' matches, and
+/// redeposits the principal. Returns the interest donated, without matches.
+pub fn harvest_interest<'info>(
+    program_id: &Pubkey,
+    accounts: &mut TransferInterestToCharity<'info>,
+    staking_accounts: &[AccountInfo<'info>],
+    matching_accounts: &[AccountInfo<'info>],
+) -> Result<u64> {
+    let pool = &mut accounts.donation_pool;
+    if pool.paused {
+        return Err(ErrorCode::PoolPaused.into());
+    }
+
+    // Unstake the collateral from Port Fi. so it can be redeemed.
+    let staking = CollateralStaking {
+        staking_accounts,
+        yield_token_vault: accounts.yield_token_vault.to_account_info(),
+        reserve: accounts.reserve.clone(),
+        lending_market: accounts.lending_market.clone(),
+        lending_market_authority: accounts.lending_market_authority.clone(),
+        pool_signer: accounts.pool_signer.to_account_info(),
+        clock: accounts.clock.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+        lending_program: accounts.lending_program.clone(),
+    };
+    staking.unstake_all(pool)?;
+    accounts.yield_token_vault.reload()?;
+
+    let exchange_rate = exchange_rate(&accounts.reserve)?
+        .collateral_to_liquidity(EXCHANGE_RATE_PRECISION)?;
+
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Approve {
+                to: accounts.yield_token_vault.to_account_info(),
+                delegate: accounts.transfer_authority.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(), //todo use user account as signer
+            },
+            pool_signer
+        );
+        token::approve(cpi_ctx, accounts.yield_token_vault.amount)?;
+    }
+
+    // withdraw from Port Finance to user's balance
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.lending_program.clone(),
+            Redeem {
+                source_collateral: accounts.yield_token_vault.to_account_info(),
+                destination_liquidity: accounts.token_vault.to_account_info(),
+                reserve: accounts.reserve.clone(),
+                reserve_liquidity_supply: accounts.reserve_liquidity_supply.clone(),
+                reserve_collateral_mint: accounts.token_mint.to_account_info(),
+                lending_market: accounts.lending_market.clone(),
+                lending_market_authority: accounts.lending_market_authority.clone(),
+                transfer_authority: accounts.pool_signer.to_account_info(),
+                clock: accounts.clock.to_account_info(),
+                token_program: accounts.token_program.to_account_info()
+            },
+            pool_signer
+        );
+        redeem(cpi_ctx, accounts.yield_token_vault.amount)?;
+    }
+
+    accounts.token_vault.reload()?;
+    // A vault short of the primary deposits has earned nothing to donate.
+    let interest = (accounts.token_vault.amount as u128).saturating_sub(pool.primary_deposits()) as u64;
+    let (fee, prize, donation) = pool.split_interest(interest);
+
+    // Value the donation in USD, for pools with a price feed. An unusable
+    // price doesn't hold up the harvest; the donation is left unpriced.
+    let donation_usd = if pool.price_feed != Pubkey::default() {
+        pyth_price(&accounts.price_feed, accounts.clock.unix_timestamp)
+            .ok()
+            .map(|(price, expo)| usd_value(donation, accounts.token_mint.decimals, price, expo))
+    } else {
+        None
+    };
+
+    // Transfer the protocol fee to the treasury.
+    if fee > 0 {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.token_vault.to_account_info(),
+                to: accounts.treasury_vault.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(),
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, fee)?;
+    }
+
+    // Transfer the lottery's share into the prize vault.
+    if prize > 0 {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.token_vault.to_account_info(),
+                to: accounts.prize_vault.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(),
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, prize)?;
+    }
+
+    // Sponsors' matches are donated along with the interest.
+    let matched = release_matches(program_id, accounts, matching_accounts, donation)?;
+    let pool = &mut accounts.donation_pool;
+    let total_donation = donation.checked_add(matched).unwrap();
+
+    let destination = if pool.qf_round != Pubkey::default() {
+        accounts.qf_vault.key()
+    } else if pool.payout_mint != Pubkey::default() {
+        accounts.payout_vault.key()
+    } else if pool.stream_duration == 0 {
+        accounts.donation_vault.key()
+    } else {
+        accounts.stream_vault.key()
+    };
+
+    if pool.qf_round != Pubkey::default() {
+        // Collect the donation for the running quadratic funding round.
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.token_vault.to_account_info(),
+                to: accounts.qf_vault.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(),
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, total_donation)?;
+    } else if pool.payout_mint != Pubkey::default() {
+        // Escrow the donation until it is swapped into the payout mint.
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.token_vault.to_account_info(),
+                to: accounts.payout_vault.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(),
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, total_donation)?;
+        pool.payout_pending = pool.payout_pending.checked_add(total_donation).unwrap();
+    } else if pool.stream_duration == 0 {
+        // Transfer tokens into the user's personal token vault.
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.token_vault.to_account_info(),
+                to: accounts.donation_vault.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(), //todo use user account as signer
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, total_donation)?;
+    } else {
+        // Escrow the donation and restart the stream with it added.
+        let now = accounts.clock.unix_timestamp;
+        let released = pool.stream_releasable(now);
+        {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: accounts.token_vault.to_account_info(),
+                    to: accounts.stream_vault.to_account_info(),
+                    authority: accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, total_donation)?;
+        }
+        if released > 0 {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: accounts.stream_vault.to_account_info(),
+                    to: accounts.donation_vault.to_account_info(),
+                    authority: accounts.pool_signer.to_account_info(),
+                },
+                pool_signer
+            );
+            token::transfer(cpi_ctx, released)?;
+        }
+
+        let unreleased = pool
+            .stream_amount
+            .checked_sub(pool.stream_released)
+            .unwrap()
+            .checked_sub(released)
+            .unwrap();
+        pool.restart_stream(unreleased.checked_add(total_donation).unwrap(), now);
+    }
+
+    pool.total_donated += donation as u128;
+    pool.total_protocol_fees += fee as u128;
+    pool.total_prizes += prize as u128;
+    if let Some(donation_usd) = donation_usd {
+        pool.total_donated_usd += donation_usd as u128;
+        pool.total_donated_priced += donation as u128;
+    }
+    pool.last_harvest_timestamp = accounts.clock.unix_timestamp;
+
+    accounts.harvest_history.load_mut()?.push(HarvestRecord {
+        timestamp: accounts.clock.unix_timestamp,
+        slot: accounts.clock.slot,
+        interest,
+        donation,
+        donation_usd: donation_usd.unwrap_or(0),
+        exchange_rate,
+        tvl: pool.total_deposited as u64,
+        destination,
+    });
+
+    // Attribute the donation not yet accrued to stakers pro-rata to their
+    // current stake.
+    pool.harvest_count = pool.harvest_count.checked_add(1).unwrap();
+    pool.credit_harvest(interest);
+
+    accounts.token_vault.reload()?;
+
+    // deposit into Port Finance
+    {
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.lending_program.clone(),
+            Deposit {
+                source_liquidity: accounts.token_vault.to_account_info(),
+                destination_collateral: accounts.yield_token_vault.to_account_info(),
+                reserve: accounts.reserve.clone(),
+                reserve_liquidity_supply: accounts.reserve_liquidity_supply.clone(),
+                reserve_collateral_mint: accounts.token_mint.to_account_info(),
+                lending_market: accounts.lending_market.clone(),
+                lending_market_authority: accounts.lending_market_authority.clone(),
+                transfer_authority: accounts.transfer_authority.clone(),
+                clock: accounts.clock.to_account_info(),
+                token_program: accounts.token_program.to_account_info()
+            },
+            pool_signer
+        );
+        deposit_reserve(cpi_ctx, accounts.token_vault.amount)?;
+    }
+
+    accounts.yield_token_vault.reload()?;
+    staking.stake(pool, accounts.yield_token_vault.amount)?;
+
+    Ok(donation)
+}
+
+/// Swaps `amount` from the pool's `payout_vault` into the payout mint through
+/// the pool's constant-product AMM (SPL Token Swap interface), paying the
+/// charity's payout vault directly.
+pub fn swap_to_payout<'info>(accounts: &SwapPayout<'info>, amount: u64, minimum_amount_out: u64) -> Result<()> {
+    if accounts.swap_program.key() != spl_token_swap_program::ID {
+        return Err(ErrorCode::SwapAccountsMismatch.into());
+    }
+
+    let ix = swap_instruction(
+        [
+            accounts.swap_pool.key(),
+            accounts.swap_authority.key(),
+            accounts.pool_signer.key(),
+            accounts.payout_vault.key(),
+            accounts.swap_source.key(),
+            accounts.swap_destination.key(),
+            accounts.payout_donation_vault.key(),
+            accounts.swap_pool_mint.key(),
+            accounts.swap_fee_account.key(),
+            accounts.token_program.key(),
+        ],
+        amount,
+        minimum_amount_out,
+    );
+
+    let pool = &accounts.donation_pool;
+    let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+    invoke_signed(
+        &ix,
+        &[
+            accounts.swap_pool.clone(),
+            accounts.swap_authority.clone(),
+            accounts.pool_signer.to_account_info(),
+            accounts.payout_vault.to_account_info(),
+            accounts.swap_source.clone(),
+            accounts.swap_destination.clone(),
+            accounts.payout_donation_vault.to_account_info(),
+            accounts.swap_pool_mint.clone(),
+            accounts.swap_fee_account.clone(),
+            accounts.token_program.to_account_info(),
+            accounts.swap_program.clone(),
+        ],
+        &[&seeds[..]],
+    )?;
+
+    Ok(())
+}
+
+/// SPL Token Swap `Swap` instruction. `keys` are in the interface's order:
+/// `[swap, swap_authority, user_transfer_authority, source, swap_source,
+/// swap_destination, destination, pool_mint, fee_account, token_program]`.
+pub fn swap_instruction(keys: [Pubkey; 10], amount_in: u64, minimum_amount_out: u64) -> Instruction {
+    let mut data = Vec::with_capacity(17);
+    data.push(SWAP_INSTRUCTION_TAG);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    Instruction {
+        program_id: spl_token_swap_program::ID,
+        accounts: vec![
+            AccountMeta::new_readonly(keys[0], false),
+            AccountMeta::new_readonly(keys[1], false),
+            AccountMeta::new_readonly(keys[2], true),
+            AccountMeta::new(keys[3], false),
+            AccountMeta::new(keys[4], false),
+            AccountMeta::new(keys[5], false),
+            AccountMeta::new(keys[6], false),
+            AccountMeta::new(keys[7], false),
+            AccountMeta::new(keys[8], false),
+            AccountMeta::new_readonly(keys[9], false),
+        ],
+        data,
+    }
+}
+
+/// Least amount of the payout mint a swap of `amount` may return: its value at
+/// the two oracle prices, less `slippage_bps`. `None` if it doesn't fit a u64.
+pub fn payout_minimum_out(
+    amount: u64,
+    decimals: u8,
+    (price, expo): (i64, i32),
+    payout_decimals: u8,
+    (payout_price, payout_expo): (i64, i32),
+    slippage_bps: u16,
+) -> Option<u64> {
+    let value = (amount as u128).checked_mul(price as u128)?;
+    let scale = expo - payout_expo + payout_decimals as i32 - decimals as i32;
+    let out = if scale >= 0 {
+        value
+            .checked_mul(10u128.checked_pow(scale as u32)?)?
+            .checked_div(payout_price as u128)?
+    } else {
+        value.checked_div((payout_price as u128).checked_mul(10u128.checked_pow((-scale) as u32)?)?)?
+    };
+    let out = out
+        .checked_mul((BPS_DENOMINATOR - slippage_bps as u64) as u128)?
+        .checked_div(BPS_DENOMINATOR as u128)?;
+    out.try_into().ok()
+}
+
+/// Liquidity mint of a Port Fi. reserve.
+pub fn reserve_liquidity_mint(reserve: &AccountInfo) -> Result<Pubkey> {
+    if *reserve.owner != port_variable_rate_lending_instructions::id() {
+        return Err(ErrorCode::ReserveMintMismatch.into());
+    }
+    let reserve = PortReserve::unpack(&reserve.try_borrow_data()?)?;
+    Ok(reserve.liquidity.mint_pubkey)
+}
+
+/// Splits the Port Fi. staking accounts off the front of `remaining`, for pools
+/// with reward staking. They are `[port_obligation, port_stake_account,
+/// staking_pool, staking_program, reserve_collateral_supply, reserve_oracle]`.
+pub fn split_staking_accounts<'a, 'info>(
+    pool: &DonationPool,
+    remaining: &'a [AccountInfo<'info>],
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    if pool.port_stake_account == Pubkey::default() {
+        return Ok(remaining.split_at(0));
+    }
+    if remaining.len() < PORT_STAKING_ACCOUNTS_LEN {
+        return Err(ErrorCode::StakingAccountsMissing.into());
+    }
+    let (staking_accounts, rest) = remaining.split_at(PORT_STAKING_ACCOUNTS_LEN);
+    if staking_accounts[0].key() != pool.port_obligation
+        || staking_accounts[1].key() != pool.port_stake_account
+        || staking_accounts[2].key() != pool.port_staking_pool
+        || staking_accounts[3].key() != port_staking_program::ID
+    {
+        return Err(ErrorCode::StakingAccountsMismatch.into());
+    }
+    Ok((staking_accounts, rest))
+}
+
+/// Cuts the user's vote in the running epoch down to the weight of their
+/// remaining stake, taking the difference off the proposal they voted for.
+/// `vote_accounts` are `[vote, proposal]`.
+pub fn withdraw_vote<'info>(
+    program_id: &Pubkey,
+    pool: &mut DonationPool,
+    user: &Account<'info, User>,
+    vote_accounts: &[AccountInfo<'info>],
+    now: i64,
+) -> Result<()> {
+    let mut vote: Account<CharityVote> = Account::try_from(&vote_accounts[0])?;
+    let mut proposal: Account<CharityProposal> = Account::try_from(&vote_accounts[1])?;
+    if vote.user != user.key()
+        || vote.epoch != pool.governance_epoch
+        || vote.proposal != proposal.key()
+    {
+        return Err(ErrorCode::VoteAccountsMismatch.into());
+    }
+
+    let weight = std::cmp::min(vote.weight, user.boosted(user.balance_staked, now) as u128);
+    let withdrawn = vote.weight.checked_sub(weight).unwrap();
+    if withdrawn == 0 {
+        return Ok(());
+    }
+    vote.weight = weight;
+    proposal.votes = proposal.votes.checked_sub(withdrawn).unwrap();
+    // Another proposal may now lead; anyone can point the pool at it with
+    // `update_leading_proposal` before the epoch is finalized.
+    if proposal.key() == pool.leading_proposal {
+        pool.leading_votes = proposal.votes;
+    }
+
+    vote.exit(program_id)?;
+    proposal.exit(program_id)?;
+    Ok(())
+}
+
+/// Redeems at least `shortfall` from the pool's secondary positions into
+/// `token_vault`, taking it off their principal. `position_accounts` hold a
+/// `[position, collateral_mint, collateral_vault, reserve,
+/// reserve_liquidity_supply, lending_market, lending_market_authority]` group
+/// per position to redeem from, in order.
+pub fn redeem_from_positions<'info>(
+    program_id: &Pubkey,
+    accounts: &mut Unstake<'info>,
+    position_accounts: &[AccountInfo<'info>],
+    shortfall: u64,
+) -> Result<()> {
+    if position_accounts.len() % POSITION_REDEEM_ACCOUNTS_LEN != 0 {
+        return Err(ErrorCode::PositionAccountsMismatch.into());
+    }
+
+    let pool = &mut accounts.donation_pool;
+    let mut shortfall = shortfall;
+    for group in position_accounts.chunks(POSITION_REDEEM_ACCOUNTS_LEN) {
+        if shortfall == 0 {
+            break;
+        }
+        let mut position: Account<ReservePosition> = Account::try_from(&group[0])?;
+        if position.donation_pool != pool.key()
+            || position.collateral_mint != group[1].key()
+            || position.collateral_vault != group[2].key()
+            || position.reserve != group[3].key()
+        {
+            return Err(ErrorCode::PositionAccountsMismatch.into());
+        }
+        let collateral_vault: Account<TokenAccount> = Account::try_from(&group[2])?;
+        // One more collateral token covers rounding in the conversion.
+        let collateral = std::cmp::min(
+            exchange_rate(&group[3])?
+                .liquidity_to_collateral(shortfall)?
+                .checked_add(1)
+                .unwrap(),
+            collateral_vault.amount,
+        );
+        if collateral == 0 {
+            continue;
+        }
+
+        let vault_before = accounts.token_vault.amount;
+        {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                accounts.lending_program.clone(),
+                Redeem {
+                    source_collateral: group[2].clone(),
+                    destination_liquidity: accounts.token_vault.to_account_info(),
+                    reserve: group[3].clone(),
+                    reserve_liquidity_supply: group[4].clone(),
+                    reserve_collateral_mint: group[1].clone(),
+                    lending_market: group[5].clone(),
+                    lending_market_authority: group[6].clone(),
+                    transfer_authority: accounts.pool_signer.to_account_info(),
+                    clock: accounts.clock.to_account_info(),
+                    token_program: accounts.token_program.to_account_info()
+                },
+                pool_signer
+            );
+            redeem(cpi_ctx, collateral)?;
+        }
+
+        // Anything above the principal is interest, donated at the next harvest.
+        accounts.token_vault.reload()?;
+        let received = accounts.token_vault.amount.checked_sub(vault_before).unwrap();
+        let principal = std::cmp::min(received, position.principal);
+        position.principal = position.principal.checked_sub(principal).unwrap();
+        pool.allocated_principal = pool.allocated_principal.checked_sub(principal as u128).unwrap();
+        shortfall = shortfall.saturating_sub(received);
+        position.exit(program_id)?;
+    }
+
+    if shortfall > 0 {
+        return Err(ErrorCode::InsufficientLiquidity.into());
+    }
+    Ok(())
+}
+
+/// Accounts to move the pool's primary collateral between `yield_token_vault`
+/// and its Port Fi. obligation, where it is staked for liquidity mining
+/// rewards. Collateral has to be unstaked before it can be redeemed.
+/// `lending_program` must come from an accounts struct that pins it to the
+/// Port Fi. lending program.
+pub struct CollateralStaking<'a, 'info> {
+    pub staking_accounts: &'a [AccountInfo<'info>],
+    pub yield_token_vault: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub pool_signer: AccountInfo<'info>,
+    pub clock: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub lending_program: AccountInfo<'info>,
+}
+
+impl<'a, 'info> CollateralStaking<'a, 'info> {
+    /// Deposits `amount` collateral from `yield_token_vault` into the pool's
+    /// obligation, staking it.
+    pub fn stake(&self, pool: &mut Account<'info, DonationPool>, amount: u64) -> Result<()> {
+        if self.staking_accounts.is_empty() || amount == 0 {
+            return Ok(());
+        }
+        let obligation = &self.staking_accounts[0];
+        let stake_account = &self.staking_accounts[1];
+        let staking_pool = &self.staking_accounts[2];
+        let staking_program = &self.staking_accounts[3];
+        let collateral_supply = &self.staking_accounts[4];
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let ix = deposit_obligation_collateral(
+            self.lending_program.key(),
+            amount,
+            self.yield_token_vault.key(),
+            collateral_supply.key(),
+            self.reserve.key(),
+            obligation.key(),
+            self.lending_market.key(),
+            self.pool_signer.key(),
+            self.pool_signer.key(),
+            Some(stake_account.key()),
+            Some(staking_pool.key()),
+        );
+        invoke_signed(
+            &ix,
+            &[
+                self.yield_token_vault.clone(),
+                collateral_supply.clone(),
+                self.reserve.clone(),
+                obligation.clone(),
+                self.lending_market.clone(),
+                self.pool_signer.clone(),
+                self.clock.clone(),
+                self.token_program.clone(),
+                stake_account.clone(),
+                staking_pool.clone(),
+                staking_program.clone(),
+                self.lending_program.clone(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        pool.staked_collateral = pool.staked_collateral.checked_add(amount).unwrap();
+        Ok(())
+    }
+
+    /// Withdraws all staked collateral from the pool's obligation back into
+    /// `yield_token_vault`.
+    pub fn unstake_all(&self, pool: &mut Account<'info, DonationPool>) -> Result<()> {
+        let amount = pool.staked_collateral;
+        if amount == 0 {
+            return Ok(());
+        }
+        if self.staking_accounts.is_empty() {
+            return Err(ErrorCode::StakingAccountsMissing.into());
+        }
+        let obligation = &self.staking_accounts[0];
+        let stake_account = &self.staking_accounts[1];
+        let staking_pool = &self.staking_accounts[2];
+        let staking_program = &self.staking_accounts[3];
+        let collateral_supply = &self.staking_accounts[4];
+        let oracle = &self.staking_accounts[5];
+
+        // Port only lets a freshly refreshed obligation withdraw.
+        let oracle_pubkey = PortReserve::unpack(&self.reserve.try_borrow_data()?)?
+            .liquidity
+            .oracle_pubkey;
+        invoke(
+            &refresh_reserve(self.lending_program.key(), self.reserve.key(), oracle_pubkey),
+            &[
+                self.reserve.clone(),
+                oracle.clone(),
+                self.clock.clone(),
+                self.lending_program.clone(),
+            ],
+        )?;
+        invoke(
+            &refresh_obligation(self.lending_program.key(), obligation.key(), vec![self.reserve.key()]),
+            &[
+                obligation.clone(),
+                self.reserve.clone(),
+                self.clock.clone(),
+                self.lending_program.clone(),
+            ],
+        )?;
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let ix = withdraw_obligation_collateral(
+            self.lending_program.key(),
+            amount,
+            collateral_supply.key(),
+            self.yield_token_vault.key(),
+            self.reserve.key(),
+            obligation.key(),
+            self.lending_market.key(),
+            self.pool_signer.key(),
+            Some(stake_account.key()),
+            Some(staking_pool.key()),
+        );
+        invoke_signed(
+            &ix,
+            &[
+                collateral_supply.clone(),
+                self.yield_token_vault.clone(),
+                self.reserve.clone(),
+                obligation.clone(),
+                self.lending_market.clone(),
+                self.lending_market_authority.clone(),
+                self.pool_signer.clone(),
+                self.clock.clone(),
+                self.token_program.clone(),
+                stake_account.clone(),
+                staking_pool.clone(),
+                staking_program.clone(),
+                self.lending_program.clone(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        pool.staked_collateral = 0;
+        Ok(())
+    }
+}
+
+/// Market value of the pool's secondary reserve positions. `position_accounts`
+/// holds a `[position, collateral_vault, reserve]` triple for each of them.
+pub fn positions_value<'info>(
+    pool: &Account<'info, DonationPool>,
+    position_accounts: &[AccountInfo<'info>],
+) -> Result<u128> {
+    if position_accounts.len() != 3 * pool.position_count as usize {
+        return Err(ErrorCode::PositionAccountsMismatch.into());
+    }
+
+    let mut value: u128 = 0;
+    for (i, triple) in position_accounts.chunks(3).enumerate() {
+        let position: Account<ReservePosition> = Account::try_from(&triple[0])?;
+        if position.donation_pool != pool.key()
+            || position.collateral_vault != triple[1].key()
+            || position.reserve != triple[2].key()
+            || position_accounts[..3 * i].chunks(3).any(|seen| seen[0].key() == triple[0].key())
+        {
+            return Err(ErrorCode::PositionAccountsMismatch.into());
+        }
+
+        let collateral_vault: Account<TokenAccount> = Account::try_from(&triple[1])?;
+        let position_value = exchange_rate(&triple[2])?
+            .collateral_to_liquidity(collateral_vault.amount)?;
+        value = value.checked_add(position_value as u128).unwrap();
+    }
+
+    Ok(value)
+}
+
+/// A sensitive pool change, as recorded by a council proposal.
+pub struct CouncilAction {
+    pub action: u8,
+    pub value: u64,
+    pub keys: [Pubkey; COUNCIL_PROPOSAL_KEYS],
+}
+
+impl CouncilAction {
+    pub fn new(action: u8, value: u64, keys: &[Pubkey]) -> Self {
+        let mut action = CouncilAction {
+            action,
+            value,
+            keys: [Pubkey::default(); COUNCIL_PROPOSAL_KEYS],
+        };
+        action.keys[..keys.len()].copy_from_slice(keys);
+        action
+    }
+}
+
+/// Checks that `action` may be applied to the pool. Without a council the pool
+/// authority must sign. With one, `council_accounts` are `[council, proposal]`
+/// for an approved, unexpired proposal of exactly this action, which is marked
+/// executed.
+pub fn authorize<'info>(
+    program_id: &Pubkey,
+    pool: &DonationPool,
+    signer: Pubkey,
+    council_accounts: &[AccountInfo<'info>],
+    now: i64,
+    action: CouncilAction,
+) -> Result<()> {
+    if pool.council == Pubkey::default() {
+        if signer != pool.authority {
+            return Err(ErrorCode::NotPoolAuthority.into());
+        }
+        return Ok(());
+    }
+
+    if council_accounts.len() != 2 {
+        return Err(ErrorCode::CouncilRequired.into());
+    }
+    let council: Account<Council> = Account::try_from(&council_accounts[0])?;
+    let mut proposal: Account<CouncilProposal> = Account::try_from(&council_accounts[1])?;
+    if council.key() != pool.council || proposal.council != council.key() {
+        return Err(ErrorCode::InvalidCouncilAction.into());
+    }
+    if proposal.executed {
+        return Err(ErrorCode::CouncilActionExecuted.into());
+    }
+    if now >= proposal.expires_at || proposal.index < council.min_proposal_index {
+        return Err(ErrorCode::CouncilActionExpired.into());
+    }
+    if proposal.approval_count < council.threshold {
+        return Err(ErrorCode::CouncilThresholdNotReached.into());
+    }
+    if proposal.action != action.action || proposal.value != action.value || proposal.keys != action.keys {
+        return Err(ErrorCode::InvalidCouncilAction.into());
+    }
+
+    proposal.executed = true;
+    proposal.exit(program_id)
+}
+
+/// Pays everything left in the current stream, vested or not, to the current
+/// charity's `donation_vault`.
+pub fn flush_stream<'info>(
+    pool: &mut Account<'info, DonationPool>,
+    stream_vault: &Account<'info, TokenAccount>,
+    donation_vault: &Account<'info, TokenAccount>,
+    pool_signer: &UncheckedAccount<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let amount = pool.stream_amount.checked_sub(pool.stream_released).unwrap();
+    if amount == 0 {
+        return Ok(());
+    }
+    pool.stream_released = pool.stream_amount;
+
+    let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+    let pool_signer_seeds = &[&seeds[..]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token::Transfer {
+            from: stream_vault.to_account_info(),
+            to: donation_vault.to_account_info(),
+            authority: pool_signer.to_account_info(),
+        },
+        pool_signer_seeds
+    );
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Pays the donations waiting for a payout swap to the current charity's
+/// `donation_vault`, in `token_mint`.
+pub fn flush_payout<'info>(
+    pool: &mut Account<'info, DonationPool>,
+    payout_vault: &Account<'info, TokenAccount>,
+    donation_vault: &Account<'info, TokenAccount>,
+    pool_signer: &UncheckedAccount<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let amount = pool.payout_pending;
+    if amount == 0 {
+        return Ok(());
+    }
+    pool.payout_pending = 0;
+
+    let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+    let pool_signer_seeds = &[&seeds[..]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token::Transfer {
+            from: payout_vault.to_account_info(),
+            to: donation_vault.to_account_info(),
+            authority: pool_signer.to_account_info(),
+        },
+        pool_signer_seeds
+    );
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Releases every sponsor match registered on the pool into the token vault,
+/// to be donated along with the interest. Returns the total matched.
+/// `matching_accounts` holds a `[matching_pool, matching_vault]` pair per
+/// registered matching pool, in registration order. Expired and exhausted
+/// matching pools are dropped.
+pub fn release_matches<'info>(
+    program_id: &Pubkey,
+    accounts: &mut TransferInterestToCharity<'info>,
+    matching_accounts: &[AccountInfo<'info>],
+    donation: u64,
+) -> Result<u64> {
+    let count = accounts.donation_pool.matching_pool_count as usize;
+    if matching_accounts.len() != 2 * count {
+        return Err(ErrorCode::MatchingPoolMismatch.into());
+    }
+
+    let mut kept = 0;
+    let mut matched: u64 = 0;
+    for i in 0..count {
+        let matching_pool_info = &matching_accounts[2 * i];
+        if matching_pool_info.key() != accounts.donation_pool.matching_pools[i] {
+            return Err(ErrorCode::MatchingPoolMismatch.into());
+        }
+        let (amount, active) = release_match(
+            program_id,
+            accounts,
+            matching_pool_info,
+            &matching_accounts[2 * i + 1],
+            donation,
+        )?;
+        matched = matched.checked_add(amount).unwrap();
+        if active {
+            accounts.donation_pool.matching_pools[kept] = matching_pool_info.key();
+            kept += 1;
+        }
+    }
+    for i in kept..count {
+        accounts.donation_pool.matching_pools[i] = Pubkey::default();
+    }
+    accounts.donation_pool.matching_pool_count = kept as u8;
+
+    Ok(matched)
+}
+
+/// Releases one sponsor's match of `donation`. Returns the amount matched and
+/// whether the matching pool can still match later harvests.
+pub fn release_match<'info>(
+    program_id: &Pubkey,
+    accounts: &TransferInterestToCharity<'info>,
+    matching_pool_info: &AccountInfo<'info>,
+    matching_vault_info: &AccountInfo<'info>,
+    donation: u64,
+) -> Result<(u64, bool)> {
+    let mut matching_pool: Account<MatchingPool> = Account::try_from(matching_pool_info)?;
+    if matching_pool.donation_pool != accounts.donation_pool.key()
+        || matching_pool.vault != matching_vault_info.key()
+    {
+        return Err(ErrorCode::MatchingPoolMismatch.into());
+    }
+    if accounts.clock.unix_timestamp >= matching_pool.end_timestamp {
+        return Ok((0, false));
+    }
+
+    let amount = std::cmp::min(
+        (donation as u128)
+            .checked_mul(matching_pool.match_bps as u128)
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR as u128)
+            .unwrap() as u64,
+        matching_pool.budget,
+    );
+    if amount == 0 {
+        return Ok((0, matching_pool.budget > 0));
+    }
+
+    matching_pool.budget = matching_pool.budget.checked_sub(amount).unwrap();
+    matching_pool.total_matched = matching_pool.total_matched.checked_add(amount).unwrap();
+
+    // Transfer the match into the token vault.
+    {
+        let pool = &accounts.donation_pool;
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: matching_vault_info.clone(),
+                to: accounts.token_vault.to_account_info(),
+                authority: accounts.pool_signer.to_account_info(),
+            },
+            pool_signer
+        );
+        token::transfer(cpi_ctx, amount)?;
+    }
+
+    matching_pool.exit(program_id)?;
+    Ok((amount, matching_pool.budget > 0))
+}
+
+/// Basis point denominator.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Lock tiers accepted by `stake_locked`: duration in seconds and recognition
+/// multiplier in basis points, for 3, 6 and 12 months.
+pub const LOCK_TIERS: [(i64, u16); 3] = [
+    (90 * 24 * 60 * 60, 12_500),
+    (180 * 24 * 60 * 60, 15_000),
+    (365 * 24 * 60 * 60, 20_000),
+];
+
+/// `DonationPool.draw_state` values.
+pub const DRAW_STATE_IDLE: u8 = 0;
+pub const DRAW_STATE_OPEN: u8 = 1;
+pub const DRAW_STATE_COMMITTED: u8 = 2;
+pub const DRAW_STATE_SEEDED: u8 = 3;
+pub const DRAW_STATE_DRAWN: u8 = 4;
+
+/// Time after a round ends during which stakers can still be entered.
+pub const DRAW_ENTRY_PERIOD: i64 = 24 * 60 * 60;
+
+/// Slots between the draw commitment and the slot whose hash seeds the draw.
+pub const DRAW_REVEAL_DELAY_SLOTS: u64 = 32;
+
+/// Slots after the seed slot during which its hash can be recorded. Kept
+/// below the 512 entries of the `SlotHashes` sysvar.
+pub const DRAW_REVEAL_WINDOW_SLOTS: u64 = 400;
+
+/// Time after the seed is recorded during which the draw can be revealed, long
+/// enough for a council to approve the reveal.
+pub const DRAW_REVEAL_PERIOD: i64 = 3 * 24 * 60 * 60;
+
+/// Time the winner has to claim the prize.
+pub const DRAW_CLAIM_PERIOD: i64 = 7 * 24 * 60 * 60;
+
+/// Time after a governance epoch ends during which `update_leading_proposal`
+/// can still correct the leader before `finalize_epoch`.
+pub const EPOCH_TALLY_PERIOD: i64 = 24 * 60 * 60;
+
+/// Drift from target, as a share of total deposits, tolerated by `rebalance`.
+pub const REBALANCE_DRIFT_BPS: u64 = 100;
+
+/// Maximum share of deposits held in secondary reserves. The rest stays in the
+/// primary reserve, which unstaking redeems from.
+pub const MAX_ALLOCATED_WEIGHT_BPS: u64 = 5_000;
+
+/// Notice given to users before a strategy migration can be executed.
+pub const MIGRATION_NOTICE_PERIOD: i64 = 7 * 24 * 60 * 60;
+
+/// Time after the notice period during which a migration can be executed.
+/// Past it the migration lapses and locks apply again.
+pub const MIGRATION_EXECUTION_WINDOW: i64 = 3 * 24 * 60 * 60;
+
+/// Number of accounts per secondary position in `redeem_from_positions`.
+pub const POSITION_REDEEM_ACCOUNTS_LEN: usize = 7;
+
+/// Number of Port Fi. staking accounts expected ahead of the other remaining
+/// accounts by instructions that move collateral of a staking pool.
+pub const PORT_STAKING_ACCOUNTS_LEN: usize = 6;
+
+/// Hard cap on `DonationPool.payout_slippage_bps`.
+pub const MAX_PAYOUT_SLIPPAGE_BPS: u16 = 500;
+
+/// SPL Token Swap `Swap` instruction tag.
+pub const SWAP_INSTRUCTION_TAG: u8 = 1;
+
+/// Maximum number of council members, bounded by `CouncilProposal.approvals`.
+pub const MAX_COUNCIL_MEMBERS: usize = 10;
+
+/// Number of target accounts a council proposal records.
+pub const COUNCIL_PROPOSAL_KEYS: usize = 5;
+
+/// Time a council proposal can be approved and executed for.
+pub const COUNCIL_PROPOSAL_TTL: i64 = 7 * 24 * 60 * 60;
+
+// Council actions, with the value and keys their proposals record.
+/// keys: `[donation_address, donation_vault]`
+pub const COUNCIL_ACTION_SET_CHARITY: u8 = 0;
+/// value: 1 to pause, 0 to unpause
+pub const COUNCIL_ACTION_SET_PAUSED: u8 = 1;
+/// value: `protocol_fee_bps`, keys: `[treasury_vault]`
+pub const COUNCIL_ACTION_SET_PROTOCOL_FEE: u8 = 2;
+/// keys: `[new_reserve, new_yield_token_mint, new_yield_token_vault]`
+pub const COUNCIL_ACTION_MIGRATE_STRATEGY: u8 = 3;
+/// value: `prize_bps`, keys: `[prize_vault]`
+pub const COUNCIL_ACTION_SET_LOTTERY: u8 = 4;
+/// value: `stream_duration`, keys: `[stream_vault]`
+pub const COUNCIL_ACTION_SET_STREAM: u8 = 5;
+/// value: `slippage_bps`, keys: `[payout_mint, payout_donation_vault,
+/// swap_pool, payout_price_feed, payout_vault]`
+pub const COUNCIL_ACTION_SET_PAYOUT: u8 = 6;
+/// keys: `[price_feed]`
+pub const COUNCIL_ACTION_SET_PRICE_FEED: u8 = 7;
+/// value: `target_weight_bps`, keys: `[reserve, collateral_mint, collateral_vault]`
+pub const COUNCIL_ACTION_SET_RESERVE_POSITION: u8 = 8;
+/// keys: `[port_obligation, port_stake_account, staking_pool, reward_vault,
+/// reward_donation_vault]`
+pub const COUNCIL_ACTION_SET_REWARD_STAKING: u8 = 9;
+/// keys: `[pending_reserve]`
+pub const COUNCIL_ACTION_CANCEL_MIGRATION: u8 = 10;
+/// value: `epoch_duration`
+pub const COUNCIL_ACTION_SET_GOVERNANCE: u8 = 11;
+/// keys: `[matching_pool]`
+pub const COUNCIL_ACTION_REGISTER_MATCH: u8 = 12;
+/// value: `end_timestamp`, keys: `[qf_round]`
+pub const COUNCIL_ACTION_CREATE_QF_ROUND: u8 = 13;
+/// keys: `[qf_round, donation_vault]`
+pub const COUNCIL_ACTION_ADD_QF_CHARITY: u8 = 14;
+/// value: `duration`
+pub const COUNCIL_ACTION_START_DRAW: u8 = 15;
+/// keys: `[commitment]`
+pub const COUNCIL_ACTION_COMMIT_DRAW: u8 = 16;
+/// keys: `[secret]`
+pub const COUNCIL_ACTION_DRAW_WINNER: u8 = 17;
+/// keys: `[pending_reserve]`
+pub const COUNCIL_ACTION_EXECUTE_MIGRATION: u8 = 18;
+/// value: `threshold`, keys: `[Council::members_key(members)]`
+pub const COUNCIL_ACTION_SET_COUNCIL: u8 = 19;
+
+/// Hard cap on `DonationPool.protocol_fee_bps`.
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
+
+/// Seconds in a 365 day year.
+pub const SECONDS_PER_YEAR: u128 = 365 * 24 * 60 * 60;
+
+/// Number of harvests kept in `HarvestHistory`.
+pub const HARVEST_HISTORY_LEN: usize = 64;
+
+/// Collateral amount `HarvestRecord.exchange_rate` is quoted for.
+pub const EXCHANGE_RATE_PRECISION: u64 = 1_000_000_000;
+
+/// Maximum number of pools in a campaign.
+pub const MAX_CAMPAIGN_POOLS: usize = 8;
+
+/// Maximum number of matching pools a donation pool can have at once.
+pub const MAX_MATCHING_POOLS: usize = 4;
+
+/// Maximum number of charities in a quadratic funding round.
+pub const MAX_QF_CHARITIES: usize = 8;
+
+/// Hash of the first slot at or after `slot` in `SlotHashes` sysvar data, or
+/// `None` when that can't be told apart from slots that aged out.
+pub fn slot_hash_at(data: &[u8], slot: u64) -> Option<[u8; 32]> {
+    let len = u64::from_le_bytes(data.get(..8)?.try_into().ok()?) as usize;
+    let mut found = None;
+    // Entries are `(slot, hash)` pairs, newest first.
+    for i in 0..len {
+        let entry = data.get(8 + i * 40..8 + (i + 1) * 40)?;
+        let entry_slot = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        if entry_slot < slot {
+            return found;
+        }
+        found = Some(entry[8..].try_into().unwrap());
+    }
+    None
+}
+
+/// Integer square root, rounded down.
+pub fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = x / 2 + x % 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// Decimals of USD amounts.
+pub const USD_DECIMALS: u8 = 6;
+
+/// Oldest Pyth price accepted, in seconds.
+pub const MAX_PRICE_AGE: i64 = 60;
+
+/// Widest Pyth confidence interval accepted, relative to the price.
+pub const MAX_PRICE_CONFIDENCE_BPS: u64 = 200;
+
+/// Pyth account magic number.
+pub const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+
+/// Pyth account type of price accounts.
+pub const PYTH_PRICE_ACCOUNT: u32 = 3;
+
+/// Pyth price status of a price that is currently trading.
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+/// Reads the aggregate price and exponent of a Pyth price account, rejecting
+/// accounts not owned by Pyth and prices that are stale, not trading or too
+/// uncertain.
+pub fn pyth_price(price_feed: &AccountInfo, now: i64) -> Result<(i64, i32)> {
+    if *price_feed.owner != pyth_program::ID {
+        return Err(ErrorCode::InvalidPriceFeed.into());
+    }
+    let data = price_feed.try_borrow_data()?;
+    if data.len() < 240
+        || u32::from_le_bytes(data[0..4].try_into().unwrap()) != PYTH_MAGIC
+        || u32::from_le_bytes(data[8..12].try_into().unwrap()) != PYTH_PRICE_ACCOUNT
+    {
+        return Err(ErrorCode::InvalidPriceFeed.into());
+    }
+
+    let expo = i32::from_le_bytes(data[20..24].try_into().unwrap());
+    let timestamp = i64::from_le_bytes(data[96..104].try_into().unwrap());
+    let price = i64::from_le_bytes(data[208..216].try_into().unwrap());
+    let conf = u64::from_le_bytes(data[216..224].try_into().unwrap());
+    let status = u32::from_le_bytes(data[224..228].try_into().unwrap());
+
+    if status != PYTH_STATUS_TRADING || price <= 0 {
+        return Err(ErrorCode::InvalidPriceFeed.into());
+    }
+    if now.checked_sub(timestamp).unwrap() > MAX_PRICE_AGE {
+        return Err(ErrorCode::StalePrice.into());
+    }
+    if (conf as u128) * (BPS_DENOMINATOR as u128)
+        > (price as u128) * (MAX_PRICE_CONFIDENCE_BPS as u128)
+    {
+        return Err(ErrorCode::PriceTooUncertain.into());
+    }
+
+    Ok((price, expo))
+}
+
+/// USD value, with `USD_DECIMALS` decimals, of `amount` tokens with `decimals`
+/// decimals at a Pyth `price * 10^expo`.
+pub fn usd_value(amount: u64, decimals: u8, price: i64, expo: i32) -> u64 {
+    let value = (amount as u128).checked_mul(price as u128).unwrap();
+    let scale = USD_DECIMALS as i32 + expo - decimals as i32;
+    let value = if scale >= 0 {
+        value.checked_mul(10u128.checked_pow(scale as u32).unwrap()).unwrap()
+    } else {
+        value.checked_div(10u128.checked_pow((-scale) as u32).unwrap()).unwrap()
+    };
+    value as u64
+}
+
+/// Scale of `DonationPool.acc_interest_per_share`.
+pub const ACC_PRECISION: u128 = 1_000_000_000_000;
+
+/// Bronze/Silver/Gold thresholds, in whole tokens of the pool's mint.
+pub const BADGE_THRESHOLDS: [u64; 3] = [10, 100, 1_000];
+
+/// Badge tier (0 = none, 3 = gold) for a lifetime attributed donation.
+pub fn badge_tier(lifetime_donated: u64, decimals: u8) -> u8 {
+    let unit = 10u64.checked_pow(decimals as u32).unwrap();
+    BADGE_THRESHOLDS
+        .iter()
+        .filter(|threshold| lifetime_donated / unit >= **threshold)
+        .count() as u8
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct Initialize<'info> {
+    #[account(zero)]
+    pub donation_pool: Account<'info, DonationPool>,
+
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_vault.mint == token_mint.key(),
+        constraint = token_vault.owner == signer.key(),
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    pub yield_token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = yield_token_vault.mint == yield_token_mint.key(),
+        constraint = yield_token_vault.owner == signer.key(),
+    )]
+    pub yield_token_vault: Account<'info, TokenAccount>,
+    pub reserve: AccountInfo<'info>,
+
+    pub donation_address: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = donation_vault.mint == token_mint.key(),
+        constraint = donation_vault.owner == donation_address.key(),
+    )]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [
+            signer.to_account_info().key.as_ref()
+        ],
+        bump = nonce,
+    )]
+    pub signer: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct CreateUser<'info> {
+    // Stake instance.
+    #[account(
+        mut,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    // Member.
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    // Misc.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+        has_one = token_vault,
+        has_one = yield_token_vault
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_vault.owner == *pool_signer.key,
+    )]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+
+    // Port Finance Accounts
+    #[account(
+        mut,
+        constraint = yield_token_vault.owner == *pool_signer.key,
+    )]
+    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
+    pub reserve: AccountInfo<'info>,
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub transfer_authority: AccountInfo<'info>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = donation_pool,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    pub user: Box<Account<'info, User>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub stake_from_account: AccountInfo<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(address = port_variable_rate_lending_instructions::id())]
+    pub lending_program: AccountInfo<'info>
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+        has_one = token_vault,
+        has_one = yield_token_vault
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_vault.owner == *pool_signer.key,
+    )]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+
+    // Port Finance Accounts
+    #[account(
+        mut,
+        constraint = yield_token_vault.owner == *pool_signer.key,
+    )]
+    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
+    pub reserve: AccountInfo<'info>,
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub transfer_authority: AccountInfo<'info>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = donation_pool,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    pub user: Box<Account<'info, User>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub receiving_vault: AccountInfo<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(address = port_variable_rate_lending_instructions::id())]
+    pub lending_program: AccountInfo<'info>
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, wsol_nonce: u8)]
+pub struct StakeSol<'info> {
+    pub stake: Stake<'info>,
+
+    // Temporary WSOL account, created and closed in-instruction.
+    #[account(
+        mut,
+        seeds = [
+            b"wsol".as_ref(),
+            stake.user.to_account_info().key.as_ref()
+        ],
+        bump = wsol_nonce,
+        constraint = stake.stake_from_account.key() == wsol_account.key(),
+        constraint = stake.token_mint.key() == spl_token::native_mint::id(),
+    )]
+    pub wsol_account: UncheckedAccount<'info>,
+
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, wsol_nonce: u8)]
+pub struct UnstakeSol<'info> {
+    pub unstake: Unstake<'info>,
+
+    // Temporary WSOL account, created and closed in-instruction.
+    #[account(
+        mut,
+        seeds = [
+            b"wsol".as_ref(),
+            unstake.user.to_account_info().key.as_ref()
+        ],
+        bump = wsol_nonce,
+        constraint = unstake.receiving_vault.key() == wsol_account.key(),
+        constraint = unstake.token_mint.key() == spl_token::native_mint::id(),
+    )]
+    pub wsol_account: UncheckedAccount<'info>,
+
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct TransferInterestToCharity<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+        has_one = token_vault,
+        has_one = donation_vault,
+        has_one = yield_token_vault,
+        has_one = treasury_vault,
+        has_one = prize_vault,
+        has_one = stream_vault,
+        has_one = qf_vault,
+        has_one = payout_vault,
+        has_one = price_feed
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_vault.owner == *pool_signer.key,
+    )]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = donation_vault.owner == donation_pool.donation_wallet,
+    )]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    // Pyth Accounts (the system program for unpriced pools)
+    pub price_feed: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub prize_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub stream_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub qf_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub payout_vault: Box<Account<'info, TokenAccount>>,
+
+    // Port Finance Accounts
+    #[account(
+        mut,
+        constraint = yield_token_vault.owner == *pool_signer.key,
+    )]
+    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
+
+    pub reserve: AccountInfo<'info>,
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub transfer_authority: AccountInfo<'info>,
+
+    // History.
+    #[account(
+        mut,
+        seeds = [
+            b"history".as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    pub harvest_history: AccountLoader<'info, HarvestHistory>,
+
+    pub owner: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(address = port_variable_rate_lending_instructions::id())]
+    pub lending_program: AccountInfo<'info>
+}
+
+#[derive(Accounts)]
+pub struct FundMatch<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    // Matching.
+    #[account(init, payer = sponsor)]
+    pub matching_pool: Box<Account<'info, MatchingPool>>,
+    #[account(
+        init,
+        payer = sponsor,
+        seeds = [
+            b"match_vault".as_ref(),
+            matching_pool.to_account_info().key.as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = pool_signer,
+    )]
+    pub matching_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+    #[account(mut)]
+    pub fund_from_account: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterMatch<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(has_one = donation_pool)]
+    pub matching_pool: Box<Account<'info, MatchingPool>>,
+
+    pub authority: Signer<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimMatch<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    // Matching.
+    #[account(
+        mut,
+        has_one = sponsor,
+        has_one = donation_pool,
+        constraint = matching_pool.vault == matching_vault.key(),
+    )]
+    pub matching_pool: Box<Account<'info, MatchingPool>>,
+    #[account(mut)]
+    pub matching_vault: Box<Account<'info, TokenAccount>>,
+
+    pub sponsor: Signer<'info>,
+    #[account(mut)]
+    pub receiving_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolFee<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(
+        constraint = treasury_vault.mint == token_mint.key(),
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetLottery<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(
+        constraint = prize_vault.mint == token_mint.key(),
+        constraint = prize_vault.owner == *pool_signer.key,
+    )]
+    pub prize_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct StartDraw<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct EnterDraw<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        mut,
+        has_one = donation_pool,
+    )]
+    pub user: Box<Account<'info, User>>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CommitDraw<'info> {
+    #[account(
+        mut,
+        has_one = prize_vault,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        mut,
+        constraint = prize_vault.owner == *pool_signer.key,
+    )]
+    pub prize_vault: Box<Account<'info, TokenAccount>>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = bond_from_account.mint == prize_vault.mint,
+    )]
+    pub bond_from_account: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct DrawWinner<'info> {
+    #[account(
+        mut,
+        has_one = prize_vault,
+        constraint = donation_pool.draw_bond_vault == bond_vault.key(),
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(mut)]
+    pub prize_vault: Box<Account<'info, TokenAccount>>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub bond_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct RecordDrawSeed<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    // Misc.
+    #[account(address = sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireDraw<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPrize<'info> {
+    #[account(
+        mut,
+        has_one = prize_vault,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(mut)]
+    pub prize_vault: Box<Account<'info, TokenAccount>>,
+
+    // User.
+    #[account(
+        has_one = owner,
+        has_one = donation_pool,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    pub user: Box<Account<'info, User>>,
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub receiving_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetStream<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(
+        constraint = stream_vault.mint == token_mint.key(),
+        constraint = stream_vault.owner == *pool_signer.key,
+    )]
+    pub stream_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseStream<'info> {
+    #[account(
+        mut,
+        has_one = stream_vault,
+        has_one = donation_vault,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(mut)]
+    pub stream_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = donation_vault.owner == donation_pool.donation_wallet,
+    )]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetGovernance<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct NominateCharity<'info> {
+    #[account(
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = proposer,
+        seeds = [
+            b"proposal".as_ref(),
+            donation_pool.to_account_info().key.as_ref(),
+            &donation_pool.governance_epoch.to_le_bytes(),
+            donation_vault.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    pub proposal: Box<Account<'info, CharityProposal>>,
+
+    pub donation_address: UncheckedAccount<'info>,
+    #[account(
+        constraint = donation_vault.mint == token_mint.key(),
+        constraint = donation_vault.owner == donation_address.key(),
+    )]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct VoteCharity<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        mut,
+        has_one = donation_pool,
+        constraint = proposal.epoch == donation_pool.governance_epoch,
+    )]
+    pub proposal: Box<Account<'info, CharityProposal>>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = donation_pool,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    pub user: Box<Account<'info, User>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // One vote per user per epoch.
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            b"vote".as_ref(),
+            user.to_account_info().key.as_ref(),
+            &donation_pool.governance_epoch.to_le_bytes()
+        ],
+        bump,
+    )]
+    pub vote: Box<Account<'info, CharityVote>>,
+
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLeadingProposal<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        has_one = donation_pool,
+        constraint = proposal.epoch == donation_pool.governance_epoch,
+    )]
+    pub proposal: Box<Account<'info, CharityProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeEpoch<'info> {
+    #[account(
+        mut,
+        has_one = stream_vault,
+        has_one = payout_vault,
+        has_one = donation_vault,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub proposal: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub stream_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub payout_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CreateQfRound<'info> {
+    #[account(
+        mut,
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    // Round.
+    #[account(init, payer = authority)]
+    pub qf_round: Box<Account<'info, QfRound>>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [
+            b"qf_vault".as_ref(),
+            qf_round.to_account_info().key.as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = pool_signer,
+    )]
+    pub matching_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [
+            b"qf_interest".as_ref(),
+            qf_round.to_account_info().key.as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = pool_signer,
+    )]
+    pub interest_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct AddQfCharity<'info> {
+    #[account(
+        has_one = token_mint,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        has_one = donation_pool,
+        constraint = !qf_round.finalized,
+    )]
+    pub qf_round: Box<Account<'info, QfRound>>,
+    #[account(
+        constraint = donation_vault.mint == token_mint.key(),
+    )]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct AllocateQf<'info> {
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        mut,
+        has_one = donation_pool,
+    )]
+    pub qf_round: Box<Account<'info, QfRound>>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = donation_pool,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    pub user: Box<Account<'info, User>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [
+            b"qf".as_ref(),
+            qf_round.to_account_info().key.as_ref(),
+            user.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    pub contribution: Box<Account<'info, QfContribution>>,
+
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeQfRound<'info> {
+    #[account(
+        mut,
+        has_one = donation_vault,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        mut,
+        has_one = donation_pool,
+        has_one = matching_vault,
+        has_one = interest_vault,
+    )]
+    pub qf_round: Box<Account<'info, QfRound>>,
+    #[account(mut)]
+    pub matching_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub interest_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimQfPot<'info> {
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(
+        has_one = donation_pool,
+        has_one = authority,
+        has_one = matching_vault,
+    )]
+    pub qf_round: Box<Account<'info, QfRound>>,
+    #[account(mut)]
+    pub matching_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub receiving_vault: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetReservePosition<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [
+            b"position".as_ref(),
+            donation_pool.to_account_info().key.as_ref(),
+            reserve.key.as_ref()
+        ],
+        bump,
+    )]
+    pub position: Box<Account<'info, ReservePosition>>,
+
+    // Port Finance Accounts
+    #[account(
+        constraint = reserve.key() != donation_pool.reserve,
+    )]
+    pub reserve: AccountInfo<'info>,
+    pub collateral_mint: Account<'info, Mint>,
+    #[account(
+        constraint = collateral_vault.mint == collateral_mint.key(),
+        constraint = collateral_vault.owner == *pool_signer.key,
+    )]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct Rebalance<'info> {
+    #[account(
+        mut,
+        has_one = token_vault,
+        has_one = yield_token_mint,
+        has_one = yield_token_vault,
+        has_one = reserve
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+
+    // Primary reserve.
+    pub yield_token_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+
+    // Secondary reserve position.
+    #[account(
+        mut,
+        has_one = donation_pool,
+        has_one = collateral_mint,
+        has_one = collateral_vault,
+        constraint = position.reserve == position_reserve.key(),
+    )]
+    pub position: Box<Account<'info, ReservePosition>>,
+    #[account(mut)]
+    pub collateral_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub position_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub position_reserve_liquidity_supply: AccountInfo<'info>,
+    pub position_lending_market: AccountInfo<'info>,
+    pub position_lending_market_authority: AccountInfo<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(address = port_variable_rate_lending_instructions::id())]
+    pub lending_program: AccountInfo<'info>
+}
+
+#[derive(Accounts)]
+pub struct ProposeStrategyMigration<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    // New Port Finance Accounts
+    pub new_reserve: AccountInfo<'info>,
+    pub new_yield_token_mint: Account<'info, Mint>,
+    #[account(
+        constraint = new_yield_token_vault.mint == new_yield_token_mint.key(),
+        constraint = new_yield_token_vault.owner == *pool_signer.key,
+    )]
+    pub new_yield_token_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CancelStrategyMigration<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub authority: Signer<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteStrategyMigration<'info> {
+    #[account(
+        mut,
+        has_one = token_vault,
+        has_one = yield_token_mint,
+        has_one = yield_token_vault,
+        has_one = reserve,
+        constraint = donation_pool.pending_reserve == new_reserve.key(),
+        constraint = donation_pool.pending_yield_token_mint == new_yield_token_mint.key(),
+        constraint = donation_pool.pending_yield_token_vault == new_yield_token_vault.key(),
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+
+    // Old Port Finance Accounts
+    #[account(mut)]
+    pub yield_token_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+
+    // New Port Finance Accounts
+    #[account(mut)]
+    pub new_yield_token_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub new_yield_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub new_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub new_reserve_liquidity_supply: AccountInfo<'info>,
+    pub new_lending_market: AccountInfo<'info>,
+    pub new_lending_market_authority: AccountInfo<'info>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(address = port_variable_rate_lending_instructions::id())]
+    pub lending_program: AccountInfo<'info>
+}
+
+#[derive(Accounts)]
+pub struct SetRewardStaking<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    // Port Finance Staking Accounts
+    #[account(
+        constraint = port_obligation.owner == *pool_signer.key,
+    )]
+    pub port_obligation: Box<Account<'info, PortObligation>>,
+    #[account(
+        constraint = port_stake_account.owner == *pool_signer.key,
+        constraint = port_stake_account.pool_pubkey == staking_pool.key(),
+    )]
+    pub port_stake_account: Box<Account<'info, PortStakeAccount>>,
+    #[account(
+        constraint = *staking_pool.owner == port_staking_program::ID,
+    )]
+    pub staking_pool: AccountInfo<'info>,
+    #[account(
+        constraint = reward_vault.owner == *pool_signer.key,
+    )]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = reward_donation_vault.mint == reward_vault.mint,
+        constraint = reward_donation_vault.key() == reward_vault.key()
+            || reward_donation_vault.owner == donation_pool.donation_wallet,
+    )]
+    pub reward_donation_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct HarvestRewards<'info> {
+    #[account(
+        mut,
+        has_one = port_stake_account,
+        has_one = reward_vault,
+        has_one = reward_donation_vault,
+        constraint = donation_pool.port_staking_pool == staking_pool.key(),
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    #[account(mut)]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub reward_donation_vault: Box<Account<'info, TokenAccount>>,
+
+    // Port Finance Staking Accounts
+    #[account(mut)]
+    pub port_stake_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub staking_pool: AccountInfo<'info>,
+    #[account(mut)]
+    pub reward_token_pool: AccountInfo<'info>,
+    pub staking_program_authority: AccountInfo<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+    #[account(address = port_staking_program::ID)]
+    pub staking_program: AccountInfo<'info>
+}
+
+#[derive(Accounts)]
+pub struct SetPayout<'info> {
     #[account(
         mut,
-        constraint = yield_token_vault.mint == yield_token_mint.key(),
-        constraint = yield_token_vault.owner == signer.key(),
+        has_one = token_mint,
     )]
-    pub yield_token_vault: Account<'info, TokenAccount>,
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub token_mint: Account<'info, Mint>,
 
-    pub donation_address: UncheckedAccount<'info>,
     #[account(
-        mut,
-        constraint = donation_vault.mint == token_mint.key(),
-        constraint = donation_vault.owner == donation_address.key(),
+        constraint = payout_mint.key() != donation_pool.token_mint,
     )]
-    pub donation_vault: Box<Account<'info, TokenAccount>>,
+    pub payout_mint: Account<'info, Mint>,
+    #[account(
+        constraint = payout_donation_vault.mint == payout_mint.key(),
+        constraint = payout_donation_vault.owner == donation_pool.donation_wallet,
+    )]
+    pub payout_donation_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = payout_vault.mint == token_mint.key(),
+        constraint = payout_vault.owner == *pool_signer.key,
+    )]
+    pub payout_vault: Box<Account<'info, TokenAccount>>,
+
+    // AMM Accounts
+    #[account(
+        constraint = *swap_pool.owner == spl_token_swap_program::ID,
+    )]
+    pub swap_pool: AccountInfo<'info>,
 
+    // Pyth Accounts
+    pub payout_price_feed: AccountInfo<'info>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
     #[account(
         seeds = [
-            signer.to_account_info().key.as_ref()
+            donation_pool.to_account_info().key.as_ref()
         ],
-        bump = nonce,
+        bump = donation_pool.nonce,
     )]
-    pub signer: UncheckedAccount<'info>
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-#[instruction(nonce: u8)]
-pub struct CreateUser<'info> {
-    // Stake instance.
+pub struct SwapPayout<'info> {
     #[account(
         mut,
+        has_one = token_mint,
+        has_one = donation_vault,
+        has_one = payout_vault,
+        has_one = payout_donation_vault,
+        has_one = swap_pool,
+        has_one = price_feed,
+        has_one = payout_price_feed,
     )]
     pub donation_pool: Box<Account<'info, DonationPool>>,
-    // Member.
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub payout_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub payout_donation_vault: Box<Account<'info, TokenAccount>>,
+
+    // Pyth Accounts
+    pub price_feed: AccountInfo<'info>,
+    pub payout_price_feed: AccountInfo<'info>,
+
+    // AMM Accounts
+    pub swap_program: AccountInfo<'info>,
+    pub swap_pool: AccountInfo<'info>,
+    pub swap_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub swap_source: AccountInfo<'info>,
+    #[account(mut)]
+    pub swap_destination: AccountInfo<'info>,
+    #[account(mut)]
+    pub swap_pool_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub swap_fee_account: AccountInfo<'info>,
+
+    // Program signers.
     #[account(
-        init_if_needed,
-        payer = owner,
         seeds = [
-            owner.key.as_ref(),
             donation_pool.to_account_info().key.as_ref()
         ],
-        bump,
+        bump = donation_pool.nonce,
     )]
-    pub user: Box<Account<'info, User>>,
+    pub pool_signer: UncheckedAccount<'info>,
 
-    #[account(mut)]
-    pub owner: Signer<'info>,
     // Misc.
-    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-pub struct Stake<'info> {
+pub struct SetPriceFeed<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+
+    // Pyth Accounts
+    pub price_feed: AccountInfo<'info>,
+
+    pub authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetCharity<'info> {
     #[account(
         mut,
         has_one = token_mint,
-        has_one = token_vault,
-        has_one = yield_token_vault
+        has_one = donation_vault,
+        has_one = stream_vault,
+        has_one = payout_vault,
     )]
     pub donation_pool: Box<Account<'info, DonationPool>>,
-    
     pub token_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub donation_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub stream_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub payout_vault: Box<Account<'info, TokenAccount>>,
+
+    pub new_donation_address: UncheckedAccount<'info>,
+    #[account(
+        constraint = new_donation_vault.mint == token_mint.key(),
+        constraint = new_donation_vault.owner == new_donation_address.key(),
+    )]
+    pub new_donation_vault: Box<Account<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = donation_pool.nonce,
+    )]
+    pub pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(mut)]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
+    pub authority: Signer<'info>,
+
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCouncil<'info> {
     #[account(
         mut,
-        constraint = token_vault.owner == *pool_signer.key,
+        has_one = authority,
+        constraint = donation_pool.council == Pubkey::default(),
     )]
-    pub token_vault: Box<Account<'info, TokenAccount>>,
+    pub donation_pool: Box<Account<'info, DonationPool>>,
 
-    // Port Finance Accounts
+    #[account(
+        init,
+        payer = authority,
+        seeds = [
+            b"council".as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    pub council: Box<Account<'info, Council>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    // Misc.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetCouncil<'info> {
+    #[account(
+        has_one = council,
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
     #[account(
         mut,
-        constraint = yield_token_vault.owner == *pool_signer.key,
+        has_one = donation_pool,
     )]
-    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
-    pub reserve: AccountInfo<'info>,
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-    pub lending_market: AccountInfo<'info>,
-    pub lending_market_authority: AccountInfo<'info>,
-    pub transfer_authority: AccountInfo<'info>,
+    pub council: Box<Account<'info, Council>>,
 
-    // User.
+    pub authority: Signer<'info>,
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeCouncilAction<'info> {
+    #[account(
+        constraint = donation_pool.council == council.key(),
+    )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
     #[account(
         mut,
-        has_one = owner,
         has_one = donation_pool,
+    )]
+    pub council: Box<Account<'info, Council>>,
+
+    #[account(
+        init,
+        payer = member,
         seeds = [
-            owner.key.as_ref(),
-            donation_pool.to_account_info().key.as_ref()
+            b"council_action".as_ref(),
+            council.to_account_info().key.as_ref(),
+            &council.proposal_count.to_le_bytes()
         ],
-        bump = user.nonce,
+        bump,
     )]
-    pub user: Box<Account<'info, User>>,
-    pub owner: Signer<'info>,
+    pub proposal: Box<Account<'info, CouncilProposal>>,
+
     #[account(mut)]
-    pub stake_from_account: Box<Account<'info, TokenAccount>>,
+    pub member: Signer<'info>,
+    // Misc.
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
 
-    // Program signers.
+#[derive(Accounts)]
+pub struct ApproveCouncilAction<'info> {
+    pub council: Box<Account<'info, Council>>,
+    #[account(
+        mut,
+        has_one = council,
+    )]
+    pub proposal: Box<Account<'info, CouncilProposal>>,
+
+    pub member: Signer<'info>,
+    // Misc.
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CreateHarvestHistory<'info> {
+    pub donation_pool: Box<Account<'info, DonationPool>>,
     #[account(
+        init,
+        payer = payer,
         seeds = [
+            b"history".as_ref(),
             donation_pool.to_account_info().key.as_ref()
         ],
-        bump = donation_pool.nonce,
+        bump,
+        space = 8 + std::mem::size_of::<HarvestHistory>(),
     )]
-    pub pool_signer: UncheckedAccount<'info>,
+    pub harvest_history: AccountLoader<'info, HarvestHistory>,
 
+    #[account(mut)]
+    pub payer: Signer<'info>,
     // Misc.
-    pub token_program: Program<'info, Token>,
-    pub clock: Sysvar<'info, Clock>,
-    pub lending_program: AccountInfo<'info>
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Unstake<'info> {
+pub struct CreateCampaign<'info> {
+    #[account(init, payer = authority)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    pub donation_address: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    // Misc.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddCampaignPool<'info> {
     #[account(
         mut,
-        has_one = token_mint,
-        has_one = token_vault,
-        has_one = yield_token_vault
+        has_one = authority,
+        has_one = donation_wallet,
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+    #[account(
+        has_one = donation_wallet,
     )]
     pub donation_pool: Box<Account<'info, DonationPool>>,
 
-    pub token_mint: Account<'info, Mint>,
+    pub donation_wallet: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct HarvestCampaign<'info> {
+    #[account(mut)]
+    pub campaign: Box<Account<'info, Campaign>>,
+}
+
+#[derive(Accounts)]
+pub struct CheckInvariants<'info> {
     #[account(
-        mut,
-        constraint = token_vault.owner == *pool_signer.key,
+        has_one = token_vault,
+        has_one = yield_token_vault,
+        has_one = reserve
     )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
     pub token_vault: Box<Account<'info, TokenAccount>>,
+    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
+    pub reserve: AccountInfo<'info>,
+}
 
-    // Port Finance Accounts
+#[derive(Accounts)]
+pub struct GetPoolStats<'info> {
     #[account(
-        mut,
-        constraint = yield_token_vault.owner == *pool_signer.key,
+        has_one = yield_token_vault,
+        has_one = reserve
     )]
+    pub donation_pool: Box<Account<'info, DonationPool>>,
     pub yield_token_vault: Box<Account<'info, TokenAccount>>,
     pub reserve: AccountInfo<'info>,
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-    pub lending_market: AccountInfo<'info>,
-    pub lending_market_authority: AccountInfo<'info>,
-    pub transfer_authority: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct MintReceipt<'info> {
+    pub donation_pool: Box<Account<'info, DonationPool>>,
 
     // User.
     #[account(
@@ -457,10 +5185,35 @@ pub struct Unstake<'info> {
         bump = user.nonce,
     )]
     pub user: Box<Account<'info, User>>,
+    #[account(mut)]
     pub owner: Signer<'info>,
 
-    #[account(mut)]
-    pub receiving_vault: Box<Account<'info, TokenAccount>>,
+    // Receipt.
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            b"receipt".as_ref(),
+            user.to_account_info().key.as_ref(),
+            &(donation_pool.harvest_count).to_le_bytes()
+        ],
+        bump,
+    )]
+    pub receipt: Box<Account<'info, DonationReceipt>>,
+    #[account(
+        init,
+        payer = owner,
+        mint::decimals = 0,
+        mint::authority = pool_signer,
+    )]
+    pub receipt_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = owner,
+    )]
+    pub receipt_token_account: Box<Account<'info, TokenAccount>>,
 
     // Program signers.
     #[account(
@@ -473,48 +5226,56 @@ pub struct Unstake<'info> {
 
     // Misc.
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
     pub clock: Sysvar<'info, Clock>,
-    pub lending_program: AccountInfo<'info>
 }
 
 #[derive(Accounts)]
-pub struct TransferInterestToCharity<'info> {
+pub struct ClaimBadge<'info> {
     #[account(
-        mut,
-        has_one = token_mint,
-        has_one = token_vault,
-        has_one = donation_vault,
-        has_one = yield_token_vault
+        has_one = token_mint,
     )]
     pub donation_pool: Box<Account<'info, DonationPool>>,
-
     pub token_mint: Account<'info, Mint>,
+
+    // User.
     #[account(
         mut,
-        constraint = token_vault.owner == *pool_signer.key,
+        has_one = owner,
+        has_one = donation_pool,
+        seeds = [
+            owner.key.as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
     )]
-    pub token_vault: Box<Account<'info, TokenAccount>>,
+    pub user: Box<Account<'info, User>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
 
+    // Badge.
     #[account(
-        mut,
-        constraint = donation_vault.owner == donation_pool.donation_wallet,
+        init_if_needed,
+        payer = owner,
+        seeds = [
+            b"badge".as_ref(),
+            donation_pool.to_account_info().key.as_ref()
+        ],
+        bump,
+        mint::decimals = 0,
+        mint::authority = pool_signer,
+        mint::freeze_authority = pool_signer,
     )]
-    pub donation_vault: Box<Account<'info, TokenAccount>>,
-
-    // Port Finance Accounts
+    pub badge_mint: Box<Account<'info, Mint>>,
     #[account(
-        mut,
-        constraint = yield_token_vault.owner == *pool_signer.key,
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = badge_mint,
+        associated_token::authority = owner,
     )]
-    pub yield_token_vault: Box<Account<'info, TokenAccount>>,
-
-    pub reserve: AccountInfo<'info>,
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-    pub lending_market: AccountInfo<'info>,
-    pub lending_market_authority: AccountInfo<'info>,
-    pub transfer_authority: AccountInfo<'info>,
-
-    pub owner: Signer<'info>,
+    pub badge_token_account: Box<Account<'info, TokenAccount>>,
 
     // Program signers.
     #[account(
@@ -527,11 +5288,13 @@ pub struct TransferInterestToCharity<'info> {
 
     // Misc.
     pub token_program: Program<'info, Token>,
-    pub clock: Sysvar<'info, Clock>,
-    pub lending_program: AccountInfo<'info>
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 #[account]
+#[derive(Default)]
 pub struct DonationPool {
     /// The total amount of tokens in the pool.
     pub total_deposited: u128,
@@ -549,12 +5312,296 @@ pub struct DonationPool {
     pub yield_token_mint: Pubkey,
     /// Port Fi. Yield Token Vault
     pub yield_token_vault: Pubkey,
+    /// Port Fi. Reserve
+    pub reserve: Pubkey,
+    /// Pool Authority
+    pub authority: Pubkey,
+    /// Protocol Fee Treasury Vault
+    pub treasury_vault: Pubkey,
+    /// Protocol fee on harvested interest, in basis points
+    pub protocol_fee_bps: u16,
+    /// The total amount of protocol fees collected
+    pub total_protocol_fees: u128,
+    /// Lottery Prize Vault
+    pub prize_vault: Pubkey,
+    /// Share of net interest awarded as a lottery prize, in basis points
+    pub prize_bps: u16,
+    /// The total amount of interest sent to the prize vault
+    pub total_prizes: u128,
+    /// Sponsor matching pools released on every harvest
+    pub matching_pools: [Pubkey; MAX_MATCHING_POOLS],
+    /// Number of entries in `matching_pools`
+    pub matching_pool_count: u8,
+    /// Current draw round
+    pub draw_round: u64,
+    /// Draw state, one of the `DRAW_STATE_*` constants
+    pub draw_state: u8,
+    /// Start of the current round
+    pub draw_start_timestamp: i64,
+    /// End of the current round
+    pub draw_end_timestamp: i64,
+    /// Tickets handed out in the current round
+    pub draw_ticket_total: u128,
+    /// Hash of the draw secret
+    pub draw_commitment: [u8; 32],
+    /// Slot whose hash seeds the committed draw
+    pub draw_reveal_slot: u64,
+    /// Hash of `draw_reveal_slot`, once recorded
+    pub draw_seed: [u8; 32],
+    /// End of the reveal period of the seeded draw
+    pub draw_reveal_deadline: i64,
+    /// Winning ticket of the current round
+    pub draw_winning_ticket: u128,
+    /// End of the prize claim period of the current round
+    pub draw_claim_deadline: i64,
+    /// Bond posted for the committed draw
+    pub draw_bond: u64,
+    /// Vault the draw bond is refunded to
+    pub draw_bond_vault: Pubkey,
+    /// Prize of the drawn round, fixed when the winner is drawn
+    pub draw_prize: u64,
+    /// Donation Stream Escrow Vault
+    pub stream_vault: Pubkey,
+    /// Seconds each harvest is streamed over, 0 to donate at harvest
+    pub stream_duration: i64,
+    /// Start of the current stream
+    pub stream_start_timestamp: i64,
+    /// End of the current stream
+    pub stream_end_timestamp: i64,
+    /// Tokens released per second by the current stream
+    pub stream_rate: u64,
+    /// The amount of tokens in the current stream
+    pub stream_amount: u64,
+    /// The amount of tokens released from the current stream
+    pub stream_released: u64,
+    /// Current charity governance epoch, 0 while governance is disabled
+    pub governance_epoch: u64,
+    /// Length of a governance epoch in seconds
+    pub epoch_duration: i64,
+    /// End of the current governance epoch
+    pub epoch_end_timestamp: i64,
+    /// Proposal with the most votes this epoch
+    pub leading_proposal: Pubkey,
+    /// Votes for the leading proposal
+    pub leading_votes: u128,
+    /// Running quadratic funding round, default when none
+    pub qf_round: Pubkey,
+    /// Vault collecting donations for `qf_round`, `donation_vault` when none
+    pub qf_vault: Pubkey,
+    /// Sum of the target weights of secondary reserve positions, pending
+    /// increases included, in basis points
+    pub allocated_weight_bps: u16,
+    /// Number of secondary reserve positions
+    pub position_count: u8,
+    /// Principal held in secondary reserve positions
+    pub allocated_principal: u128,
+    /// Reserve of the pending strategy migration
+    pub pending_reserve: Pubkey,
+    /// Collateral mint of the pending strategy migration
+    pub pending_yield_token_mint: Pubkey,
+    /// Collateral vault of the pending strategy migration
+    pub pending_yield_token_vault: Pubkey,
+    /// Earliest execution time of the pending migration, 0 when none is pending
+    pub migration_execute_after: i64,
+    /// Port Fi. Staking Account
+    pub port_stake_account: Pubkey,
+    /// Port Fi. obligation holding the staked collateral
+    pub port_obligation: Pubkey,
+    /// Port Fi. staking pool of the primary reserve
+    pub port_staking_pool: Pubkey,
+    /// Primary collateral deposited into `port_obligation` and staked
+    pub staked_collateral: u64,
+    /// Vault receiving claimed Port Fi. rewards
+    pub reward_vault: Pubkey,
+    /// Charity vault rewards are forwarded to, `reward_vault` to keep them
+    pub reward_donation_vault: Pubkey,
+    /// The total amount of reward tokens donated to charity
+    pub total_rewards_donated: u128,
+    /// Mint the charity is paid in, default when paid in `token_mint`
+    pub payout_mint: Pubkey,
+    /// Decimals of `payout_mint`
+    pub payout_decimals: u8,
+    /// Charity vault for `payout_mint`
+    pub payout_donation_vault: Pubkey,
+    /// SPL Token Swap pool used to swap into `payout_mint`
+    pub swap_pool: Pubkey,
+    /// Pyth price account of `payout_mint`
+    pub payout_price_feed: Pubkey,
+    /// Slippage below the oracle price accepted on payout swaps
+    pub payout_slippage_bps: u16,
+    /// Pool vault harvested donations wait in for `swap_payout`
+    pub payout_vault: Pubkey,
+    /// Donation waiting in `payout_vault`
+    pub payout_pending: u64,
+    /// Pyth price account donations are valued with, default when unpriced
+    pub price_feed: Pubkey,
+    /// The total amount donated, in USD with `USD_DECIMALS` decimals
+    pub total_donated_usd: u128,
+    /// The part of `total_donated` that was valued in USD
+    pub total_donated_priced: u128,
+    /// Council that must approve sensitive changes, default when none
+    pub council: Pubkey,
+    /// Whether staking and harvesting are paused
+    pub paused: bool,
     /// User Count
     pub user_stake_count: u64,
+    /// Number of harvests so far
+    pub harvest_count: u64,
+    /// Time of the last harvest
+    pub last_harvest_timestamp: i64,
+    /// Donated interest per staked token, scaled by `ACC_PRECISION`
+    pub acc_interest_per_share: u128,
+    /// Primary reserve interest already credited to `acc_interest_per_share`
+    /// ahead of the next harvest
+    pub accrued_interest: u64,
     /// nonce
     pub nonce: u8
 }
 
+impl DonationPool {
+    /// Principal held in the primary reserve.
+    pub fn primary_deposits(&self) -> u128 {
+        self.total_deposited.checked_sub(self.allocated_principal).unwrap()
+    }
+
+    /// Splits `interest` into the protocol fee, the prize share and the donation.
+    pub fn split_interest(&self, interest: u64) -> (u64, u64, u64) {
+        let fee = (interest as u128)
+            .checked_mul(self.protocol_fee_bps as u128)
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR as u128)
+            .unwrap() as u64;
+        let net_interest = interest.checked_sub(fee).unwrap();
+        let prize = (net_interest as u128)
+            .checked_mul(self.prize_bps as u128)
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR as u128)
+            .unwrap() as u64;
+        let donation = net_interest.checked_sub(prize).unwrap();
+        (fee, prize, donation)
+    }
+
+    /// Credits the donation share of primary reserve interest earned since the
+    /// last accrual to the current stakers, before the stake changes.
+    /// `primary_value` is the primary collateral's value in `token_mint`.
+    pub fn accrue_interest(&mut self, primary_value: u64) {
+        let interest = (primary_value as u128).saturating_sub(self.primary_deposits()) as u64;
+        let earned = interest.saturating_sub(self.accrued_interest);
+        let (_, _, donation) = self.split_interest(earned);
+        self.credit_donation(donation);
+        self.accrued_interest = self.accrued_interest.checked_add(earned).unwrap();
+    }
+
+    /// Credits the donation share of harvested `interest` not yet accrued.
+    pub fn credit_harvest(&mut self, interest: u64) {
+        let earned = interest.saturating_sub(self.accrued_interest);
+        let (_, _, donation) = self.split_interest(earned);
+        self.credit_donation(donation);
+        self.accrued_interest = 0;
+    }
+
+    /// Attributes `donation` to stakers pro-rata to their current stake.
+    fn credit_donation(&mut self, donation: u64) {
+        if self.total_deposited > 0 {
+            self.acc_interest_per_share = self
+                .acc_interest_per_share
+                .checked_add(
+                    (donation as u128)
+                        .checked_mul(ACC_PRECISION)
+                        .unwrap()
+                        .checked_div(self.total_deposited)
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Pays future donations to `donation_wallet`. Rewards are kept in
+    /// `reward_vault`, and donations paid in `token_mint`, until the new
+    /// charity's reward vault and payout are set. Flush the payout escrow first.
+    pub fn change_charity(&mut self, donation_wallet: Pubkey, donation_vault: Pubkey) {
+        self.donation_wallet = donation_wallet;
+        self.donation_vault = donation_vault;
+        self.reward_donation_vault = self.reward_vault;
+        self.payout_mint = Pubkey::default();
+        self.payout_decimals = 0;
+        self.payout_donation_vault = Pubkey::default();
+        self.swap_pool = Pubkey::default();
+        self.payout_price_feed = Pubkey::default();
+        self.payout_slippage_bps = 0;
+        self.payout_vault = donation_vault;
+    }
+
+    /// Streamed donation that has vested but not yet been released.
+    pub fn stream_releasable(&self, now: i64) -> u64 {
+        let duration = self.stream_end_timestamp.saturating_sub(self.stream_start_timestamp);
+        let vested = if duration <= 0 || now >= self.stream_end_timestamp {
+            self.stream_amount
+        } else {
+            let elapsed = std::cmp::max(now - self.stream_start_timestamp, 0);
+            (self.stream_amount as u128)
+                .checked_mul(elapsed as u128)
+                .unwrap()
+                .checked_div(duration as u128)
+                .unwrap() as u64
+        };
+        vested.checked_sub(self.stream_released).unwrap()
+    }
+
+    /// USD value of `amount` at the average price of priced donations.
+    pub fn average_usd_value(&self, amount: u64) -> u64 {
+        if self.total_donated_priced == 0 {
+            return 0;
+        }
+        (amount as u128)
+            .checked_mul(self.total_donated_usd)
+            .unwrap()
+            .checked_div(self.total_donated_priced)
+            .unwrap() as u64
+    }
+
+    /// Whether a strategy migration is announced and can still be executed.
+    pub fn migration_pending(&self, now: i64) -> bool {
+        self.migration_execute_after != 0
+            && now < self.migration_execute_after.checked_add(MIGRATION_EXECUTION_WINDOW).unwrap()
+    }
+
+    /// Forgets the pending strategy migration.
+    pub fn clear_migration(&mut self) {
+        self.pending_reserve = Pubkey::default();
+        self.pending_yield_token_mint = Pubkey::default();
+        self.pending_yield_token_vault = Pubkey::default();
+        self.migration_execute_after = 0;
+    }
+
+    /// Drops `matching_pool` from the pools released on harvest.
+    pub fn remove_matching_pool(&mut self, matching_pool: Pubkey) {
+        let count = self.matching_pool_count as usize;
+        if let Some(i) = self.matching_pools[..count].iter().position(|key| *key == matching_pool) {
+            self.matching_pools.copy_within(i + 1..count, i);
+            self.matching_pools[count - 1] = Pubkey::default();
+            self.matching_pool_count -= 1;
+        }
+    }
+
+    /// Moves governance to the next epoch.
+    pub fn start_epoch(&mut self, now: i64) {
+        self.governance_epoch = self.governance_epoch.checked_add(1).unwrap();
+        self.epoch_end_timestamp = now.checked_add(self.epoch_duration).unwrap();
+        self.leading_proposal = Pubkey::default();
+        self.leading_votes = 0;
+    }
+
+    /// Starts streaming `amount` linearly over `stream_duration` from `now`.
+    pub fn restart_stream(&mut self, amount: u64, now: i64) {
+        self.stream_start_timestamp = now;
+        self.stream_end_timestamp = now.checked_add(self.stream_duration).unwrap();
+        self.stream_rate = amount.checked_div(self.stream_duration as u64).unwrap();
+        self.stream_amount = amount;
+        self.stream_released = 0;
+    }
+}
+
 #[account]
 #[derive(Default)]
 pub struct User {
@@ -564,14 +5611,936 @@ pub struct User {
     pub owner: Pubkey,
     /// The amount staked.
     pub balance_staked: u64,
+    /// `acc_interest_per_share` already accounted for on the current stake.
+    pub interest_debt: u128,
+    /// Donated interest attributed to this user.
+    pub attributed_donation: u64,
+    /// Donated interest attributed to this user over its lifetime.
+    pub lifetime_donated_attributed: u64,
+    /// Attributed donations already covered by a receipt.
+    pub receipted_donation: u64,
+    /// Highest supporter badge tier claimed.
+    pub badge_tier: u8,
+    /// Draw round `draw_weight` was accrued for.
+    pub draw_round: u64,
+    /// Balance-seconds staked during `draw_round`.
+    pub draw_weight: u128,
+    /// Time `draw_weight` was last accrued up to.
+    pub draw_weight_timestamp: i64,
+    /// Last draw round the user entered.
+    pub ticket_round: u64,
+    /// First ticket held in `ticket_round`.
+    pub ticket_start: u128,
+    /// One past the last ticket held in `ticket_round`.
+    pub ticket_end: u128,
+    /// Stake that can't be withdrawn before `lock_until`.
+    pub locked_balance: u64,
+    /// Maturity of the locked stake.
+    pub lock_until: i64,
+    /// Recognition multiplier of the lock tier, in basis points.
+    pub lock_multiplier_bps: u16,
+    /// Attributed donation allocated to quadratic funding rounds.
+    pub qf_allocated: u64,
+    /// Last governance epoch the user voted in; stake stays put until it ends.
+    pub voted_epoch: u64,
     /// Signer nonce.
     pub nonce: u8,
 }
 
+impl User {
+    /// Credits donations accrued on the current stake since the last settlement.
+    /// The lifetime (leaderboard) figure is boosted for the locked stake's share.
+    pub fn settle_donation(&mut self, pool: &DonationPool, now: i64) {
+        let accrued = self.accrued_interest(pool);
+        let pending = accrued.checked_sub(self.interest_debt).unwrap() as u64;
+        let boosted = self.boosted(pending, now);
+        self.attributed_donation = self.attributed_donation.checked_add(pending).unwrap();
+        self.lifetime_donated_attributed = self.lifetime_donated_attributed.checked_add(boosted).unwrap();
+        self.interest_debt = accrued;
+    }
+
+    /// `amount` earned on the whole stake, with the locked stake's share of it
+    /// multiplied by the lock tier.
+    pub fn boosted(&self, amount: u64, now: i64) -> u64 {
+        if self.balance_staked == 0 {
+            return amount;
+        }
+        let locked_share = (amount as u128)
+            .checked_mul(self.locked_balance as u128)
+            .unwrap()
+            .checked_div(self.balance_staked as u128)
+            .unwrap();
+        let bonus = locked_share
+            .checked_mul((self.lock_multiplier(now) as u128).saturating_sub(BPS_DENOMINATOR as u128))
+            .unwrap()
+            .checked_div(BPS_DENOMINATOR as u128)
+            .unwrap();
+        (amount as u128).checked_add(bonus).unwrap() as u64
+    }
+
+    /// Multiplier of the user's lock tier, 1x once the lock has matured.
+    pub fn lock_multiplier(&self, now: i64) -> u16 {
+        if now < self.lock_until {
+            self.lock_multiplier_bps
+        } else {
+            BPS_DENOMINATOR as u16
+        }
+    }
+
+    /// Replaces `previous` of the user's quadratic funding allocations with
+    /// `allocated`. Allocations across rounds can't exceed the user's
+    /// attributed donation.
+    pub fn reallocate_qf(&mut self, previous: u64, allocated: u64) -> Result<()> {
+        let qf_allocated = self
+            .qf_allocated
+            .checked_sub(previous)
+            .unwrap()
+            .checked_add(allocated)
+            .unwrap();
+        if qf_allocated > self.attributed_donation {
+            return Err(ErrorCode::QfAllocationExceedsAttributed.into());
+        }
+        self.qf_allocated = qf_allocated;
+        Ok(())
+    }
+
+    /// Resets the debt after `balance_staked` changes.
+    pub fn reset_interest_debt(&mut self, pool: &DonationPool) {
+        self.interest_debt = self.accrued_interest(pool);
+    }
+
+    /// Accrues balance-seconds for the pool's current draw round up to `now`.
+    pub fn accrue_draw_weight(&mut self, pool: &DonationPool, now: i64) {
+        if self.draw_round != pool.draw_round {
+            self.draw_round = pool.draw_round;
+            self.draw_weight = 0;
+            self.draw_weight_timestamp = pool.draw_start_timestamp;
+        }
+
+        let from = std::cmp::max(self.draw_weight_timestamp, pool.draw_start_timestamp);
+        let until = std::cmp::min(now, pool.draw_end_timestamp);
+        if until > from {
+            let weight = (self.balance_staked as u128)
+                .checked_mul((until - from) as u128)
+                .unwrap();
+            self.draw_weight = self.draw_weight.checked_add(weight).unwrap();
+            self.draw_weight_timestamp = until;
+        }
+    }
+
+    fn accrued_interest(&self, pool: &DonationPool) -> u128 {
+        (self.balance_staked as u128)
+            .checked_mul(pool.acc_interest_per_share)
+            .unwrap()
+            .checked_div(ACC_PRECISION)
+            .unwrap()
+    }
+}
+
+#[account(zero_copy)]
+pub struct HarvestHistory {
+    /// Pool the history belongs to.
+    pub donation_pool: Pubkey,
+    /// Index the next record is written to.
+    pub head: u64,
+    /// Number of records written, capped at `HARVEST_HISTORY_LEN`.
+    pub count: u64,
+    /// Ring buffer of the most recent harvests.
+    pub records: [HarvestRecord; HARVEST_HISTORY_LEN],
+}
+
+impl HarvestHistory {
+    /// Writes a record, overwriting the oldest once the buffer is full.
+    pub fn push(&mut self, record: HarvestRecord) {
+        self.records[self.head as usize] = record;
+        self.head = (self.head + 1) % HARVEST_HISTORY_LEN as u64;
+        if (self.count as usize) < HARVEST_HISTORY_LEN {
+            self.count += 1;
+        }
+    }
+}
+
+#[zero_copy]
+pub struct HarvestRecord {
+    /// Time of the harvest.
+    pub timestamp: i64,
+    /// Slot of the harvest.
+    pub slot: u64,
+    /// Interest harvested, before the protocol fee and the prize share.
+    pub interest: u64,
+    /// Part of `interest` donated.
+    pub donation: u64,
+    /// Donation valued in USD, zero for unpriced pools or an unusable price.
+    pub donation_usd: u64,
+    /// Underlying value of `EXCHANGE_RATE_PRECISION` collateral tokens.
+    pub exchange_rate: u64,
+    /// Total deposited at the time of the harvest.
+    pub tvl: u64,
+    /// Vault the donation was sent to: the charity's vault, the payout
+    /// vault, the stream vault or the quadratic funding vault.
+    pub destination: Pubkey,
+}
+
+#[account]
+#[derive(Default)]
+pub struct Council {
+    /// Pool the council governs.
+    pub donation_pool: Pubkey,
+    /// Member keys, the first `member_count` are set.
+    pub members: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub member_count: u8,
+    /// Approvals needed to execute an action.
+    pub threshold: u8,
+    /// Number of actions proposed, used to derive the next proposal.
+    pub proposal_count: u64,
+    /// Proposals below this index were made to an earlier membership and
+    /// are void.
+    pub min_proposal_index: u64,
+}
+
+impl Council {
+    /// Key a `COUNCIL_ACTION_SET_COUNCIL` proposal records for `members`.
+    pub fn members_key(members: &[Pubkey]) -> Pubkey {
+        let members: Vec<&[u8]> = members.iter().map(|member| member.as_ref()).collect();
+        Pubkey::new_from_array(hashv(&members).to_bytes())
+    }
+
+    /// Sets distinct `members`, at most `MAX_COUNCIL_MEMBERS`, of which
+    /// `threshold` must approve an action.
+    pub fn set_members(&mut self, members: &[Pubkey], threshold: u8) -> Result<()> {
+        if members.is_empty() || members.len() > MAX_COUNCIL_MEMBERS {
+            return Err(ErrorCode::InvalidCouncil.into());
+        }
+        if threshold == 0 || threshold as usize > members.len() {
+            return Err(ErrorCode::InvalidCouncil.into());
+        }
+        for (i, member) in members.iter().enumerate() {
+            if members[..i].contains(member) {
+                return Err(ErrorCode::InvalidCouncil.into());
+            }
+        }
+
+        self.members = [Pubkey::default(); MAX_COUNCIL_MEMBERS];
+        self.members[..members.len()].copy_from_slice(members);
+        self.member_count = members.len() as u8;
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    pub fn member_index(&self, key: &Pubkey) -> Option<usize> {
+        self.members[..self.member_count as usize]
+            .iter()
+            .position(|member| member == key)
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct CouncilProposal {
+    /// Council the action was proposed to.
+    pub council: Pubkey,
+    /// Index of the proposal within the council.
+    pub index: u64,
+    /// One of the `COUNCIL_ACTION_*` constants.
+    pub action: u8,
+    /// Argument of the action's setter, depending on `action`.
+    pub value: u64,
+    /// Target accounts, depending on `action`.
+    pub keys: [Pubkey; COUNCIL_PROPOSAL_KEYS],
+    /// Time after which the proposal can no longer be approved or executed.
+    pub expires_at: i64,
+    /// Bitmask of members that approved.
+    pub approvals: u16,
+    pub approval_count: u8,
+    pub executed: bool,
+}
+
+#[account]
+#[derive(Default)]
+pub struct CharityProposal {
+    /// Pool the charity is nominated for.
+    pub donation_pool: Pubkey,
+    /// Governance epoch of the nomination.
+    pub epoch: u64,
+    /// Nominated charity wallet.
+    pub donation_wallet: Pubkey,
+    /// Nominated charity's donation vault.
+    pub donation_vault: Pubkey,
+    /// Who nominated the charity.
+    pub proposer: Pubkey,
+    /// Stake-weighted votes received.
+    pub votes: u128,
+}
+
+#[account]
+#[derive(Default)]
+pub struct CharityVote {
+    /// Voting user account.
+    pub user: Pubkey,
+    /// Proposal voted for.
+    pub proposal: Pubkey,
+    /// Governance epoch of the vote.
+    pub epoch: u64,
+    /// Weight of the vote.
+    pub weight: u128,
+}
+
+#[account]
+#[derive(Default)]
+pub struct QfRound {
+    /// Pool whose users take part in the round.
+    pub donation_pool: Pubkey,
+    /// Who created the round, and gets back what's left of the matching pot.
+    pub authority: Pubkey,
+    /// Vault holding the matching pot.
+    pub matching_vault: Pubkey,
+    /// Vault collecting the pool's donations during the round.
+    pub interest_vault: Pubkey,
+    /// Time after which allocations close.
+    pub end_timestamp: i64,
+    /// Donation vaults of the charities in the round.
+    pub charities: [Pubkey; MAX_QF_CHARITIES],
+    /// Number of charities.
+    pub charity_count: u8,
+    /// Sum of contributions per charity.
+    pub contributions: [u128; MAX_QF_CHARITIES],
+    /// Sum of the square roots of contributions per charity.
+    pub sqrt_contributions: [u128; MAX_QF_CHARITIES],
+    /// Matching paid to each charity at finalization.
+    pub allocations: [u64; MAX_QF_CHARITIES],
+    /// Round donations paid to each charity at finalization.
+    pub donations: [u64; MAX_QF_CHARITIES],
+    /// Whether the matching pot has been distributed.
+    pub finalized: bool,
+}
+
+impl QfRound {
+    /// Moves a contribution to charity `index` from `old` to `amount`.
+    pub fn reallocate(&mut self, index: usize, old: u64, amount: u64) {
+        self.contributions[index] = self.contributions[index]
+            .checked_sub(old as u128)
+            .unwrap()
+            .checked_add(amount as u128)
+            .unwrap();
+        self.sqrt_contributions[index] = self.sqrt_contributions[index]
+            .checked_sub(isqrt(old as u128))
+            .unwrap()
+            .checked_add(isqrt(amount as u128))
+            .unwrap();
+    }
+
+    /// Share of the round's collected `interest` owed to charity `index`. If
+    /// less was collected than allocated, every charity is scaled down alike.
+    pub fn interest_share(&self, index: usize, interest: u64) -> u64 {
+        let count = self.charity_count as usize;
+        let total_contributions: u128 = self.contributions[..count].iter().sum();
+        let interest_base = std::cmp::max(total_contributions, interest as u128);
+        (interest as u128)
+            .checked_mul(self.contributions[index])
+            .unwrap()
+            .checked_div(interest_base)
+            .unwrap_or(0) as u64
+    }
+
+    /// Splits the matching `pot` by match weight. A charity's weight is (sum
+    /// of sqrt contributions)^2 minus what was contributed, favouring many
+    /// small supporters. Nothing is allocated without any match weight.
+    pub fn match_allocations(&self, pot: u64) -> [u64; MAX_QF_CHARITIES] {
+        let count = self.charity_count as usize;
+        let mut weights = [0u128; MAX_QF_CHARITIES];
+        let mut total_weight: u128 = 0;
+        for i in 0..count {
+            weights[i] = self.sqrt_contributions[i]
+                .checked_mul(self.sqrt_contributions[i])
+                .unwrap()
+                .saturating_sub(self.contributions[i]);
+            total_weight = total_weight.checked_add(weights[i]).unwrap();
+        }
+
+        let mut allocations = [0u64; MAX_QF_CHARITIES];
+        if total_weight == 0 {
+            return allocations;
+        }
+        for i in 0..count {
+            allocations[i] = (pot as u128)
+                .checked_mul(weights[i])
+                .unwrap()
+                .checked_div(total_weight)
+                .unwrap() as u64;
+        }
+        allocations
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct QfContribution {
+    /// Round contributed to.
+    pub qf_round: Pubkey,
+    /// Contributing user account.
+    pub user: Pubkey,
+    /// Attributed donation allocated to each charity.
+    pub amounts: [u64; MAX_QF_CHARITIES],
+    /// Sum of `amounts`.
+    pub total: u64,
+}
+
+#[account]
+#[derive(Default)]
+pub struct ReservePosition {
+    /// Pool the position belongs to.
+    pub donation_pool: Pubkey,
+    /// Port Fi. reserve of the position.
+    pub reserve: Pubkey,
+    /// Reserve's collateral mint.
+    pub collateral_mint: Pubkey,
+    /// Vault holding the position's collateral.
+    pub collateral_vault: Pubkey,
+    /// Target share of deposits, in basis points.
+    pub target_weight_bps: u16,
+    /// Increased target weight waiting for its notice period, in basis points.
+    pub pending_weight_bps: u16,
+    /// Time `pending_weight_bps` takes effect, 0 when none is pending.
+    pub weight_effective_after: i64,
+    /// Principal deposited into the reserve.
+    pub principal: u64,
+}
+
+impl ReservePosition {
+    /// Applies a pending weight increase once its notice period is over.
+    pub fn settle_weight(&mut self, now: i64) {
+        if self.weight_effective_after != 0 && now >= self.weight_effective_after {
+            self.target_weight_bps = self.pending_weight_bps;
+            self.pending_weight_bps = 0;
+            self.weight_effective_after = 0;
+        }
+    }
+
+    /// Target weight the position is headed for, pending increase included.
+    pub fn requested_weight_bps(&self) -> u16 {
+        if self.weight_effective_after != 0 {
+            self.pending_weight_bps
+        } else {
+            self.target_weight_bps
+        }
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct MatchingPool {
+    /// Sponsor funding the match.
+    pub sponsor: Pubkey,
+    /// Pool whose donations are matched.
+    pub donation_pool: Pubkey,
+    /// Vault holding the sponsor's budget.
+    pub vault: Pubkey,
+    /// Match per donated token, in basis points.
+    pub match_bps: u16,
+    /// Time after which no more matches are released.
+    pub end_timestamp: i64,
+    /// Budget left to match with.
+    pub budget: u64,
+    /// Total released to the charity.
+    pub total_matched: u64,
+}
+
+#[account]
+#[derive(Default)]
+pub struct Campaign {
+    /// Campaign creator, allowed to add pools.
+    pub authority: Pubkey,
+    /// Charity wallet shared by every pool in the campaign.
+    pub donation_wallet: Pubkey,
+    /// Member pools.
+    pub pools: [Pubkey; MAX_CAMPAIGN_POOLS],
+    /// Token mint of each member pool.
+    pub token_mints: [Pubkey; MAX_CAMPAIGN_POOLS],
+    /// Total donated to the campaign's charity by each member pool, in that
+    /// pool's `token_mints` entry. Amounts in different mints are never added
+    /// up; see `Campaign::total_donated_in`.
+    pub total_donated: [u128; MAX_CAMPAIGN_POOLS],
+    /// Number of member pools.
+    pub pool_count: u8,
+}
+
+impl Campaign {
+    /// Total donated through the campaign in `mint`, over every member pool of
+    /// that mint.
+    pub fn total_donated_in(&self, mint: Pubkey) -> u128 {
+        (0..self.pool_count as usize)
+            .filter(|i| self.token_mints[*i] == mint)
+            .map(|i| self.total_donated[i])
+            .sum()
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct DonationReceipt {
+    /// Pool the donation came from.
+    pub donation_pool: Pubkey,
+    /// The donor.
+    pub owner: Pubkey,
+    /// The pool's charity wallet when the receipt was minted. If the pool
+    /// changed charity since the owner's previous receipt, part of `amount`
+    /// went to an earlier charity.
+    pub donation_wallet: Pubkey,
+    /// Receipt token mint.
+    pub receipt_mint: Pubkey,
+    /// Number of harvests the pool had made when the receipt was minted.
+    pub period: u64,
+    /// Donated interest attributed to the owner since their previous receipt.
+    pub amount: u64,
+    /// Estimate of `amount` in USD at the pool's all-time average donation
+    /// price, not at the prices of the harvests it came from.
+    pub amount_usd: u64,
+    /// Time the receipt was minted.
+    pub timestamp: i64,
+}
+
+/// Return data of `get_pool_stats`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolStats {
+    /// Current value of the pool's collateral in the underlying token.
+    pub underlying_value: u64,
+    /// Interest accrued since the last harvest.
+    pub pending_interest: u64,
+    /// Total donated to charity.
+    pub total_donated: u128,
+    /// Total donated to charity, in USD.
+    pub total_donated_usd: u128,
+    /// Number of users.
+    pub user_count: u64,
+    /// APY estimated from interest since the last harvest, in basis points.
+    pub estimated_apy_bps: u64,
+}
+
 #[error]
 pub enum ErrorCode {
     #[msg("Insufficient funds to unstake.")]
     InsufficientFundUnstake,
     #[msg("Amount must be greater than zero.")]
     AmountMustBeGreaterThanZero,
+    #[msg("No harvest to mint a receipt for.")]
+    NoHarvestToReceipt,
+    #[msg("Receipt already minted for this harvest.")]
+    ReceiptAlreadyMinted,
+    #[msg("Attributed donations have not reached the next badge tier.")]
+    BadgeTierNotReached,
+    #[msg("Campaign has no room for more pools.")]
+    CampaignFull,
+    #[msg("Pool is already part of this campaign.")]
+    PoolAlreadyInCampaign,
+    #[msg("Accounts do not match the campaign's pools.")]
+    CampaignPoolMismatch,
+    #[msg("Stake vault received more than the transferred amount.")]
+    StakeAmountMismatch,
+    #[msg("Redeemable collateral is worth less than total deposits.")]
+    CollateralValueBelowDeposits,
+    #[msg("Token vault holds idle funds.")]
+    UnexpectedIdleFunds,
+    #[msg("User count is inconsistent with deposits.")]
+    UserCountInconsistent,
+    #[msg("Protocol fee exceeds the maximum.")]
+    ProtocolFeeTooHigh,
+    #[msg("Accounts do not match the matching pool.")]
+    MatchingPoolMismatch,
+    #[msg("Matching period has ended.")]
+    MatchingPeriodEnded,
+    #[msg("Matching period has not ended.")]
+    MatchingPeriodNotEnded,
+    #[msg("Pool already has the maximum number of matching pools.")]
+    MatchingPoolsFull,
+    #[msg("Matching pool is already registered.")]
+    MatchingPoolAlreadyRegistered,
+    #[msg("Prize share must be at most 100%.")]
+    InvalidPrizeSplit,
+    #[msg("Draw is not in the right state.")]
+    InvalidDrawState,
+    #[msg("User already entered this draw.")]
+    AlreadyEnteredDraw,
+    #[msg("Secret does not match the draw commitment.")]
+    InvalidDrawSecret,
+    #[msg("The pool already has a running quadratic funding round.")]
+    QfRoundActive,
+    #[msg("The user's vote and the proposal it went to are missing.")]
+    VoteAccountsMissing,
+    #[msg("Accounts do not match the user's vote.")]
+    VoteAccountsMismatch,
+    #[msg("The draw's slot hash is no longer available.")]
+    DrawRevealExpired,
+    #[msg("User does not hold the winning ticket.")]
+    NotDrawWinner,
+    #[msg("Stream duration must not be negative.")]
+    InvalidStreamDuration,
+    #[msg("Stream vault still holds unreleased donations.")]
+    StreamInProgress,
+    #[msg("Governance epoch is not active.")]
+    EpochNotActive,
+    #[msg("Governance epoch has not ended.")]
+    EpochNotEnded,
+    #[msg("Proposal is not the epoch's leading proposal.")]
+    NotLeadingProposal,
+    #[msg("Quadratic funding round has ended.")]
+    QfRoundEnded,
+    #[msg("Quadratic funding round has not ended.")]
+    QfRoundNotEnded,
+    #[msg("Quadratic funding round is already finalized.")]
+    QfRoundFinalized,
+    #[msg("Quadratic funding round has no room for more charities.")]
+    QfRoundFull,
+    #[msg("Charity is already part of this round.")]
+    QfCharityAlreadyAdded,
+    #[msg("Charity is not part of this round.")]
+    QfCharityNotFound,
+    #[msg("Allocation exceeds the user's attributed donations.")]
+    QfAllocationExceedsAttributed,
+    #[msg("Lock tier must be 0 (3 months), 1 (6 months) or 2 (12 months).")]
+    InvalidLockTier,
+    #[msg("Stake is locked until maturity.")]
+    StakeLocked,
+    #[msg("Reserve target weights exceed the allocation cap.")]
+    ReserveWeightsTooHigh,
+    #[msg("Accounts do not match the pool's reserve positions.")]
+    PositionAccountsMismatch,
+    #[msg("The pool's reserves can't cover the withdrawal.")]
+    InsufficientLiquidity,
+    #[msg("Reserve does not lend the pool's token.")]
+    ReserveMintMismatch,
+    #[msg("The strategy migration was not executed in time.")]
+    MigrationExpired,
+    #[msg("Position is within its drift tolerance.")]
+    NoRebalanceNeeded,
+    #[msg("No strategy migration is pending.")]
+    NoMigrationPending,
+    #[msg("Strategy migration notice period has not passed.")]
+    MigrationNoticePeriod,
+    #[msg("Donations are still waiting in the payout vault to be swapped.")]
+    PayoutPending,
+    #[msg("Swap accounts do not match the pool's payout configuration.")]
+    SwapAccountsMismatch,
+    #[msg("Payout swaps can't be combined with streaming.")]
+    PayoutWithStream,
+    #[msg("Payout swaps need the pool's price feed.")]
+    PriceFeedRequired,
+    #[msg("Payout slippage exceeds the maximum.")]
+    SlippageTooHigh,
+    #[msg("Price feed is not a trading Pyth price account.")]
+    InvalidPriceFeed,
+    #[msg("Price feed is stale.")]
+    StalePrice,
+    #[msg("Price feed confidence interval is too wide.")]
+    PriceTooUncertain,
+    #[msg("Pool is paused.")]
+    PoolPaused,
+    #[msg("Council members or threshold are invalid.")]
+    InvalidCouncil,
+    #[msg("Signer is not a council member.")]
+    NotCouncilMember,
+    #[msg("Council action or its accounts are invalid.")]
+    InvalidCouncilAction,
+    #[msg("Council member already approved this action.")]
+    AlreadyApproved,
+    #[msg("Council action has already been executed.")]
+    CouncilActionExecuted,
+    #[msg("Council action has not reached its approval threshold.")]
+    CouncilThresholdNotReached,
+    #[msg("This change must go through the pool's council.")]
+    CouncilRequired,
+    #[msg("Council action has expired.")]
+    CouncilActionExpired,
+    #[msg("Signer is not the pool authority.")]
+    NotPoolAuthority,
+    #[msg("Port staking accounts are required for pools with reward staking.")]
+    StakingAccountsMissing,
+    #[msg("Port staking accounts do not match the pool's staking configuration.")]
+    StakingAccountsMismatch,
+    #[msg("Staked collateral must be withdrawn before changing reward staking.")]
+    CollateralStaked,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn isqrt_rounds_down() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(2), 1);
+        assert_eq!(isqrt(3), 1);
+        assert_eq!(isqrt(4), 2);
+        assert_eq!(isqrt(15), 3);
+        assert_eq!(isqrt(16), 4);
+        assert_eq!(isqrt(17), 4);
+        assert_eq!(isqrt(1_000_000), 1_000);
+        assert_eq!(isqrt(999_999), 999);
+    }
+
+    #[test]
+    fn isqrt_handles_the_full_range() {
+        assert_eq!(isqrt(u64::MAX as u128), u32::MAX as u128);
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        for n in [2u128, 10, 12_345, 1 << 64, (1 << 100) + 7] {
+            let root = isqrt(n);
+            assert!(root * root <= n);
+            assert!((root + 1) * (root + 1) > n);
+        }
+    }
+
+    #[test]
+    fn slot_hash_at_finds_the_committed_slot() {
+        let data = slot_hashes(&[105, 104, 103, 101, 100]);
+        assert_eq!(slot_hash_at(&data, 103), Some([103; 32]));
+        // Skipped slots resolve to the next slot that was produced.
+        assert_eq!(slot_hash_at(&data, 102), Some([103; 32]));
+    }
+
+    #[test]
+    fn slot_hash_at_rejects_unavailable_slots() {
+        let data = slot_hashes(&[105, 104, 103]);
+        // Not produced yet.
+        assert_eq!(slot_hash_at(&data, 106), None);
+        // Possibly aged out.
+        assert_eq!(slot_hash_at(&data, 103), None);
+        assert_eq!(slot_hash_at(&data, 90), None);
+        assert_eq!(slot_hash_at(&[], 1), None);
+    }
+
+    fn stake(pool: &mut DonationPool, user: &mut User, primary_value: u64, amount: u64) {
+        pool.accrue_interest(primary_value);
+        user.settle_donation(pool, 0);
+        user.balance_staked += amount;
+        user.reset_interest_debt(pool);
+        pool.total_deposited += amount as u128;
+    }
+
+    #[test]
+    fn interest_is_attributed_to_the_stake_that_earned_it() {
+        let mut pool = DonationPool::default();
+        let mut early = User::default();
+        let mut late = User::default();
+
+        stake(&mut pool, &mut early, 0, 1_000);
+        // 100 of interest accrues before the second user stakes just ahead of
+        // the harvest.
+        stake(&mut pool, &mut late, 1_100, 1_000);
+        pool.credit_harvest(100);
+
+        early.settle_donation(&pool, 0);
+        late.settle_donation(&pool, 0);
+        assert_eq!(early.attributed_donation, 100);
+        assert_eq!(late.attributed_donation, 0);
+        assert_eq!(pool.accrued_interest, 0);
+    }
+
+    #[test]
+    fn interest_after_the_last_accrual_is_shared_at_harvest() {
+        let mut pool = DonationPool::default();
+        pool.protocol_fee_bps = 1_000;
+        let mut early = User::default();
+        let mut late = User::default();
+
+        stake(&mut pool, &mut early, 0, 1_000);
+        stake(&mut pool, &mut late, 1_100, 1_000);
+        // Another 200 accrues on both stakes before the harvest.
+        pool.credit_harvest(300);
+
+        early.settle_donation(&pool, 0);
+        late.settle_donation(&pool, 0);
+        // 90 + 90 net of the 10% fee for the early user, 90 for the late one.
+        assert_eq!(early.attributed_donation, 180);
+        assert_eq!(late.attributed_donation, 90);
+    }
+
+    fn qf_round(contributions: &[&[u64]]) -> QfRound {
+        let mut round = QfRound::default();
+        round.charity_count = contributions.len() as u8;
+        for (i, amounts) in contributions.iter().enumerate() {
+            for amount in amounts.iter() {
+                round.reallocate(i, 0, *amount);
+            }
+        }
+        round
+    }
+
+    #[test]
+    fn qf_match_favours_many_small_contributions() {
+        // Four supporters of 100 against one of 400: (4 * 10)^2 - 400 = 1200
+        // against 20^2 - 400 = 0.
+        let round = qf_round(&[&[100, 100, 100, 100], &[400]]);
+        assert_eq!(round.contributions[..2], [400, 400]);
+        assert_eq!(round.sqrt_contributions[..2], [40, 20]);
+        assert_eq!(round.match_allocations(1_000)[..2], [1_000, 0]);
+
+        // Weights of 1200 and 800 (two supporters of 400).
+        let round = qf_round(&[&[100, 100, 100, 100], &[400, 400]]);
+        assert_eq!(round.match_allocations(1_000)[..2], [600, 400]);
+    }
+
+    #[test]
+    fn qf_reallocation_replaces_the_previous_contribution() {
+        let mut round = qf_round(&[&[100, 100]]);
+        round.reallocate(0, 100, 400);
+        assert_eq!(round.contributions[0], 500);
+        assert_eq!(round.sqrt_contributions[0], 30);
+    }
+
+    #[test]
+    fn qf_round_donations_follow_allocations() {
+        let round = qf_round(&[&[300], &[100]]);
+        // Less collected than allocated scales every charity down alike.
+        assert_eq!(round.interest_share(0, 200), 150);
+        assert_eq!(round.interest_share(1, 200), 50);
+        // More collected pays the allocations, the rest is unallocated.
+        assert_eq!(round.interest_share(0, 800), 300);
+        assert_eq!(round.interest_share(1, 800), 100);
+        // Nothing allocated, nothing paid by allocation.
+        assert_eq!(qf_round(&[&[]]).interest_share(0, 800), 0);
+    }
+
+    #[test]
+    fn qf_pot_is_left_to_reclaim_without_match_weight() {
+        // Single supporters earn no match, so the whole pot is reclaimable.
+        let round = qf_round(&[&[400], &[900]]);
+        let pot = 1_000;
+        let allocations = round.match_allocations(pot);
+        assert_eq!(allocations, [0; MAX_QF_CHARITIES]);
+        assert_eq!(pot - allocations.iter().sum::<u64>(), pot);
+
+        // Rounding dust stays for the reclaim too.
+        let round = qf_round(&[&[100, 100, 100, 100], &[100, 100], &[100, 100]]);
+        let allocations = round.match_allocations(pot);
+        assert_eq!(allocations[..3], [750, 125, 125]);
+        let round = qf_round(&[&[100, 100], &[100, 100], &[100, 100]]);
+        let allocations = round.match_allocations(pot);
+        assert_eq!(allocations[..3], [333, 333, 333]);
+        assert_eq!(pot - allocations.iter().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn qf_allocations_use_up_attributed_donation() {
+        let mut user = User::default();
+        user.attributed_donation = 100;
+
+        // One round: 60 to one charity, then 40 to another.
+        user.reallocate_qf(0, 60).unwrap();
+        user.reallocate_qf(60, 100).unwrap();
+        assert!(user.reallocate_qf(100, 101).is_err());
+        // Lowering the round's allocation frees it up again.
+        user.reallocate_qf(100, 80).unwrap();
+        assert_eq!(user.qf_allocated, 80);
+
+        // A later round only gets what's left, until more is attributed.
+        assert!(user.reallocate_qf(0, 30).is_err());
+        user.reallocate_qf(0, 20).unwrap();
+        user.attributed_donation = 130;
+        user.reallocate_qf(20, 50).unwrap();
+        assert_eq!(user.qf_allocated, 130);
+    }
+
+    #[test]
+    fn campaign_totals_are_kept_per_mint() {
+        let usdc = Pubkey::new_unique();
+        let sol = Pubkey::new_unique();
+        let mut campaign = Campaign::default();
+        campaign.token_mints[..3].copy_from_slice(&[usdc, sol, usdc]);
+        campaign.total_donated[..3].copy_from_slice(&[1_000_000, 5, 2_000_000]);
+        campaign.pool_count = 3;
+
+        assert_eq!(campaign.total_donated_in(usdc), 3_000_000);
+        assert_eq!(campaign.total_donated_in(sol), 5);
+        assert_eq!(campaign.total_donated_in(Pubkey::new_unique()), 0);
+    }
+
+    #[test]
+    fn council_members_can_be_replaced() {
+        let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut council = Council::default();
+        council.set_members(&members, 2).unwrap();
+        assert_eq!(council.member_index(&members[2]), Some(2));
+
+        council.set_members(&members[..1], 1).unwrap();
+        assert_eq!(council.member_count, 1);
+        assert_eq!(council.member_index(&members[2]), None);
+
+        assert!(council.set_members(&[members[0], members[0]], 1).is_err());
+        assert!(council.set_members(&members, 4).is_err());
+        assert_ne!(Council::members_key(&members), Council::members_key(&members[..2]));
+    }
+
+    #[test]
+    fn charity_change_stops_paying_out_to_the_old_charity() {
+        let mut pool = DonationPool {
+            payout_mint: Pubkey::new_unique(),
+            payout_decimals: 6,
+            payout_donation_vault: Pubkey::new_unique(),
+            swap_pool: Pubkey::new_unique(),
+            payout_price_feed: Pubkey::new_unique(),
+            payout_slippage_bps: 100,
+            payout_vault: Pubkey::new_unique(),
+            ..DonationPool::default()
+        };
+        let donation_vault = Pubkey::new_unique();
+        pool.change_charity(Pubkey::new_unique(), donation_vault);
+
+        assert_eq!(pool.payout_mint, Pubkey::default());
+        assert_eq!(pool.payout_donation_vault, Pubkey::default());
+        assert_eq!(pool.swap_pool, Pubkey::default());
+        assert_eq!(pool.payout_price_feed, Pubkey::default());
+        assert_eq!(pool.payout_vault, donation_vault);
+    }
+
+    #[test]
+    fn charity_change_stops_forwarding_rewards_to_the_old_charity() {
+        let mut pool = DonationPool {
+            reward_vault: Pubkey::new_unique(),
+            reward_donation_vault: Pubkey::new_unique(),
+            ..DonationPool::default()
+        };
+        let donation_wallet = Pubkey::new_unique();
+        let donation_vault = Pubkey::new_unique();
+        pool.change_charity(donation_wallet, donation_vault);
+
+        assert_eq!(pool.donation_wallet, donation_wallet);
+        assert_eq!(pool.donation_vault, donation_vault);
+        assert_eq!(pool.reward_donation_vault, pool.reward_vault);
+    }
+
+    #[test]
+    fn payout_minimum_out_converts_at_oracle_prices() {
+        // 1 token at $1 into a 9 decimal mint at $2, with 1% slippage.
+        assert_eq!(
+            payout_minimum_out(1_000_000, 6, (100_000_000, -8), 9, (200_000_000, -8), 100),
+            Some(495_000_000)
+        );
+        // Prices with different exponents.
+        assert_eq!(
+            payout_minimum_out(1_000_000, 6, (1_000_000, -6), 6, (100_000_000, -8), 0),
+            Some(1_000_000)
+        );
+        // Into a mint with fewer decimals.
+        assert_eq!(
+            payout_minimum_out(1_000_000_000, 9, (100_000_000, -8), 6, (100_000_000, -8), 0),
+            Some(1_000_000)
+        );
+    }
+
+    #[test]
+    fn payout_minimum_out_rejects_overflow() {
+        assert_eq!(
+            payout_minimum_out(u64::MAX, 0, (i64::MAX, 0), 18, (1, 0), 0),
+            None
+        );
+    }
 }
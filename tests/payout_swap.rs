@@ -0,0 +1,295 @@
+//! `swap_payout` against the in-repo mock AMM, deployed at the SPL Token Swap
+//! program id the pool pins.
+
+use anchor_lang::{AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use lossless_donation::{
+    pyth_program, spl_token_swap_program, DonationPool, ErrorCode, PYTH_MAGIC, PYTH_PRICE_ACCOUNT,
+    PYTH_STATUS_TRADING,
+};
+use mock_amm::{SwapState, EXCEEDED_SLIPPAGE};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{Instruction, InstructionError},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Signer,
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+const DECIMALS: u8 = 6;
+/// $1 with Pyth's usual exponent.
+const ONE_DOLLAR: i64 = 100_000_000;
+const SLIPPAGE_BPS: u16 = 100;
+
+struct Setup {
+    context: ProgramTestContext,
+    accounts: lossless_donation::accounts::SwapPayout,
+}
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // The entrypoint wants its accounts to outlive the instruction.
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    lossless_donation::entry(program_id, accounts, data)
+}
+
+fn add_mint(program_test: &mut ProgramTest) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    program_test.add_packable_account(
+        mint,
+        u32::MAX as u64,
+        &Mint {
+            mint_authority: COption::None,
+            supply: u64::MAX,
+            decimals: DECIMALS,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &spl_token::id(),
+    );
+    mint
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+    let account = Pubkey::new_unique();
+    program_test.add_packable_account(
+        account,
+        u32::MAX as u64,
+        &TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &spl_token::id(),
+    );
+    account
+}
+
+/// A Pyth price account quoting `price` at `timestamp`.
+fn price_account(price: i64, timestamp: i64) -> Account {
+    let mut data = vec![0; 240];
+    data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+    data[8..12].copy_from_slice(&PYTH_PRICE_ACCOUNT.to_le_bytes());
+    data[20..24].copy_from_slice(&(-8i32).to_le_bytes());
+    data[96..104].copy_from_slice(&timestamp.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[224..228].copy_from_slice(&PYTH_STATUS_TRADING.to_le_bytes());
+    Account {
+        lamports: u32::MAX as u64,
+        data,
+        owner: pyth_program::ID,
+        ..Account::default()
+    }
+}
+
+/// A pool with `pending` donation escrowed for a payout mint priced at
+/// `payout_price`, swapped through a token/payout pool with the given reserves.
+async fn setup(token_reserve: u64, payout_reserve: u64, pending: u64, payout_price: i64) -> Setup {
+    let mut program_test = ProgramTest::new(
+        "lossless_donation",
+        lossless_donation::id(),
+        processor!(process_instruction),
+    );
+    program_test.add_program(
+        "mock_amm",
+        spl_token_swap_program::ID,
+        processor!(mock_amm::process_instruction),
+    );
+
+    let donation_pool = Pubkey::new_unique();
+    let (pool_signer, nonce) = Pubkey::find_program_address(&[donation_pool.as_ref()], &lossless_donation::id());
+    let swap_pool = Pubkey::new_unique();
+    let (swap_authority, swap_nonce) =
+        Pubkey::find_program_address(&[swap_pool.as_ref()], &spl_token_swap_program::ID);
+    let donation_wallet = Pubkey::new_unique();
+
+    let token_mint = add_mint(&mut program_test);
+    let payout_mint = add_mint(&mut program_test);
+    let payout_vault = add_token_account(&mut program_test, token_mint, pool_signer, pending);
+    let donation_vault = add_token_account(&mut program_test, token_mint, donation_wallet, 0);
+    let payout_donation_vault = add_token_account(&mut program_test, payout_mint, donation_wallet, 0);
+    let swap_source = add_token_account(&mut program_test, token_mint, swap_authority, token_reserve);
+    let swap_destination = add_token_account(&mut program_test, payout_mint, swap_authority, payout_reserve);
+    let swap_pool_mint = add_mint(&mut program_test);
+    let swap_fee_account = add_token_account(&mut program_test, swap_pool_mint, swap_authority, 0);
+    let price_feed = Pubkey::new_unique();
+    let payout_price_feed = Pubkey::new_unique();
+
+    program_test.add_account(
+        swap_pool,
+        Account {
+            lamports: u32::MAX as u64,
+            data: SwapState { nonce: swap_nonce, token_a: swap_source, token_b: swap_destination }.pack(),
+            owner: spl_token_swap_program::ID,
+            ..Account::default()
+        },
+    );
+
+    let pool = DonationPool {
+        donation_wallet,
+        donation_vault,
+        token_mint,
+        price_feed,
+        payout_mint,
+        payout_decimals: DECIMALS,
+        payout_donation_vault,
+        swap_pool,
+        payout_price_feed,
+        payout_slippage_bps: SLIPPAGE_BPS,
+        payout_vault,
+        payout_pending: pending,
+        nonce,
+        ..DonationPool::default()
+    };
+    let mut data = Vec::new();
+    pool.try_serialize(&mut data).unwrap();
+    program_test.add_account(
+        donation_pool,
+        Account {
+            lamports: u32::MAX as u64,
+            data,
+            owner: lossless_donation::id(),
+            ..Account::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    context.set_account(&price_feed, &price_account(ONE_DOLLAR, now).into());
+    context.set_account(&payout_price_feed, &price_account(payout_price, now).into());
+
+    Setup {
+        context,
+        accounts: lossless_donation::accounts::SwapPayout {
+            donation_pool,
+            token_mint,
+            payout_vault,
+            donation_vault,
+            payout_donation_vault,
+            price_feed,
+            payout_price_feed,
+            swap_program: spl_token_swap_program::ID,
+            swap_pool,
+            swap_authority,
+            swap_source,
+            swap_destination,
+            swap_pool_mint,
+            swap_fee_account,
+            pool_signer,
+            token_program: spl_token::id(),
+            clock: sysvar::clock::id(),
+        },
+    }
+}
+
+async fn swap_payout(setup: &mut Setup) -> Result<(), TransactionError> {
+    let instruction = Instruction {
+        program_id: lossless_donation::id(),
+        accounts: setup.accounts.to_account_metas(None),
+        data: lossless_donation::instruction::SwapPayout {}.data(),
+    };
+    let recent_blockhash = setup.context.banks_client.get_recent_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&setup.context.payer.pubkey()),
+        &[&setup.context.payer],
+        recent_blockhash,
+    );
+    setup
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+async fn balance(setup: &mut Setup, account: Pubkey) -> u64 {
+    let account = setup.context.banks_client.get_account(account).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+async fn payout_pending(setup: &mut Setup) -> u64 {
+    let account = setup
+        .context
+        .banks_client
+        .get_account(setup.accounts.donation_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    DonationPool::try_deserialize(&mut account.data.as_ref()).unwrap().payout_pending
+}
+
+fn program_error(error: ErrorCode) -> TransactionError {
+    match ProgramError::from(error) {
+        ProgramError::Custom(code) => TransactionError::InstructionError(0, InstructionError::Custom(code)),
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[tokio::test]
+async fn swap_payout_pays_the_charity_at_the_oracle_price() {
+    let pending = 1_000_000;
+    let mut setup = setup(1_000_000_000_000, 1_000_000_000_000, pending, ONE_DOLLAR).await;
+
+    swap_payout(&mut setup).await.unwrap();
+
+    let payout_donation_vault = setup.accounts.payout_donation_vault;
+    assert_eq!(balance(&mut setup, payout_donation_vault).await, 999_999);
+    let payout_vault = setup.accounts.payout_vault;
+    assert_eq!(balance(&mut setup, payout_vault).await, 0);
+    assert_eq!(payout_pending(&mut setup).await, 0);
+}
+
+#[tokio::test]
+async fn swap_payout_fails_against_a_skewed_pool() {
+    // The pool prices the payout mint at $2, twice its oracle price, as it
+    // would after a sandwiching trade.
+    let pending = 1_000_000;
+    let mut setup = setup(1_000_000_000_000, 500_000_000_000, pending, ONE_DOLLAR).await;
+
+    assert_eq!(
+        swap_payout(&mut setup).await,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(EXCEEDED_SLIPPAGE)))
+    );
+
+    let payout_donation_vault = setup.accounts.payout_donation_vault;
+    assert_eq!(balance(&mut setup, payout_donation_vault).await, 0);
+    let payout_vault = setup.accounts.payout_vault;
+    assert_eq!(balance(&mut setup, payout_vault).await, pending);
+    assert_eq!(payout_pending(&mut setup).await, pending);
+}
+
+#[tokio::test]
+async fn swap_payout_refuses_an_unpinned_swap_program() {
+    let pending = 1_000_000;
+    let mut setup = setup(1_000_000_000_000, 1_000_000_000_000, pending, ONE_DOLLAR).await;
+    setup.accounts.swap_program = spl_token::id();
+
+    assert_eq!(swap_payout(&mut setup).await, Err(program_error(ErrorCode::SwapAccountsMismatch)));
+
+    let payout_vault = setup.accounts.payout_vault;
+    assert_eq!(balance(&mut setup, payout_vault).await, pending);
+}
+
+#[tokio::test]
+async fn swap_payout_pays_in_the_token_without_a_usable_price() {
+    let pending = 1_000_000;
+    let mut setup = setup(1_000_000_000_000, 1_000_000_000_000, pending, 0).await;
+
+    swap_payout(&mut setup).await.unwrap();
+
+    let donation_vault = setup.accounts.donation_vault;
+    assert_eq!(balance(&mut setup, donation_vault).await, pending);
+    let payout_donation_vault = setup.accounts.payout_donation_vault;
+    assert_eq!(balance(&mut setup, payout_donation_vault).await, 0);
+    assert_eq!(payout_pending(&mut setup).await, 0);
+}